@@ -14,6 +14,50 @@ pub struct Position {
     pub y: i32,
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving,
+}
+
+/// Counts down the player's turns until they get hungrier.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub duration: i32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct ProvidesFood {}
+
+/// Whoever kills an entity earns XP for its level, see `DamageSystem`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+}
+
+/// Filled by `speed` every tick, the entity acts once it holds a turn's worth.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Energy {
+    pub speed: i32,
+    pub current: i32,
+}
+
+/// Given to the entities whose turn it is, taken back once they have acted.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct MyTurn {}
+
+/// Replaces `Position` while the entity's level isn't the current one.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct OtherLevelPosition {
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32,
+}
+
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
@@ -35,6 +79,73 @@ pub struct Viewshed {
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Monster {}
 
+/// What a monster is up to, see `MonsterAI`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum AiState {
+    /// Wanders around.
+    Idle,
+    /// Hunts the player, who was last seen at x, y.
+    Chasing { x: i32, y: i32 },
+    /// Runs away from the player.
+    Fleeing,
+    /// Lost sight of the player, and goes to look where they were for a few turns.
+    Searching { x: i32, y: i32, turns: i32 },
+}
+
+/// How a monster behaves, set by its kind in the raws.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Brain {
+    pub state: AiState,
+    /// Runs away below this percentage of its health, never at 0.
+    pub flee_below: i32,
+    /// Turns it keeps searching for a player it lost sight of.
+    pub memory: i32,
+    /// Searches where the player actually is, not where they were last seen.
+    pub relentless: bool,
+}
+
+/// The side a creature is on. How factions treat each other is in the raws.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Faction {
+    pub name: String,
+}
+
+/// What a spell does.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum SpellEffect {
+    /// A bolt flying at the target, hurting the first creature in its way.
+    Bolt { damage: i32 },
+    /// Heals a hurt ally.
+    HealAlly { amount: i32 },
+    /// Brings a monster of the named kind next to the caster.
+    Summon { mob: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Spell {
+    pub name: String,
+    pub effect: SpellEffect,
+    pub range: i32,
+    /// Turns to wait after casting it.
+    pub cooldown: i32,
+    /// Turns left before it can be cast again.
+    pub ready_in: i32,
+}
+
+/// The spells a monster knows, in the order it prefers them.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SpellBook {
+    pub spells: Vec<Spell>,
+}
+
+/// Cast the `spell`th spell of the caster's book at `target`. Never saved, it
+/// is resolved in the turn it is made.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToCast {
+    pub spell: usize,
+    pub target: Entity,
+}
+
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct Name {
     pub name: String,
@@ -47,8 +158,23 @@ pub struct BlocksTile {}
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
+}
+
+/// Natural abilities, 10 is average. See `gamesystem::attr_bonus`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Attributes {
+    pub might: i32,
+    pub fitness: i32,
+    pub quickness: i32,
+    pub intelligence: i32,
+}
+
+/// Trained bonuses to hitting and to not being hit.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Skills {
+    pub melee: i32,
     pub defense: i32,
-    pub power: i32,
+    pub ranged: i32,
 }
 
 // ConvertSaveLoad? todo
@@ -57,20 +183,27 @@ pub struct WantsToMelee {
     pub target: Entity,
 }
 
-#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Damage to apply this turn, each hit with whoever dealt it. Applied before
+/// the turn ends, so it is never saved.
+#[derive(Component, Debug, Clone)]
 pub struct SufferDamage {
-    pub amount: Vec<i32>,
+    pub amount: Vec<(i32, Entity)>,
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(
+        store: &mut WriteStorage<SufferDamage>,
+        victim: Entity,
+        amount: i32,
+        from: Entity,
+    ) {
         // if victim already has dmg component, add damage to the component
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, from));
         } else {
             // add suffer damage component to the enemy with the first dmg to take
             let dmg = SufferDamage {
-                amount: vec![amount],
+                amount: vec![(amount, from)],
             };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
@@ -78,7 +211,10 @@ impl SufferDamage {
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
-pub struct Item {}
+pub struct Item {
+    /// In kilograms, for one of a stack.
+    pub weight: f32,
+}
 
 #[derive(Component, Debug, ConvertSaveload)]
 pub struct InBackpack {
@@ -125,13 +261,258 @@ pub struct AreaOfEffect {
     pub radius: i32
 }
 
-#[derive(Component, Debug, ConvertSaveload, Clone)]
-pub struct Confusion {
-    pub turns: i32
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StatusKind {
+    Poison,
+    Burning,
+    Regeneration,
+    Haste,
+    Slow,
+    Blindness,
+    Confusion,
+}
+
+/// How a status combines with one of its kind that is already there.
+pub enum Stacking {
+    /// Potencies add up, the longest duration is kept.
+    Intensity,
+    /// The strongest potency and the longest duration are kept.
+    Refresh,
+    /// Durations add up.
+    Duration,
+}
+
+impl StatusKind {
+    /// As in "You are ...".
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "poisoned",
+            StatusKind::Burning => "burning",
+            StatusKind::Regeneration => "regenerating",
+            StatusKind::Haste => "hasted",
+            StatusKind::Slow => "slowed",
+            StatusKind::Blindness => "blind",
+            StatusKind::Confusion => "confused",
+        }
+    }
+
+    /// Glyph and color shown in the status bar.
+    pub fn icon(&self) -> (char, (u8, u8, u8)) {
+        match self {
+            StatusKind::Poison => ('♣', rltk::GREEN),
+            StatusKind::Burning => ('☼', rltk::ORANGE),
+            StatusKind::Regeneration => ('♥', rltk::PINK),
+            StatusKind::Haste => ('»', rltk::CYAN),
+            StatusKind::Slow => ('«', rltk::SLATEBLUE),
+            StatusKind::Blindness => ('○', rltk::GREY),
+            StatusKind::Confusion => ('?', rltk::MAGENTA),
+        }
+    }
+
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Poison => Stacking::Intensity,
+            StatusKind::Confusion => Stacking::Duration,
+            _ => Stacking::Refresh,
+        }
+    }
+
+    /// Damage or healing per turn, or how strong the effect is.
+    pub fn default_potency(&self) -> i32 {
+        match self {
+            StatusKind::Poison | StatusKind::Regeneration => 1,
+            StatusKind::Burning => 2,
+            _ => 0,
+        }
+    }
+
+    /// The status this one cancels out.
+    pub fn opposite(&self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Haste => Some(StatusKind::Slow),
+            StatusKind::Slow => Some(StatusKind::Haste),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// Turns left, counted in player turns like the hunger clock.
+    pub turns: i32,
+    pub potency: i32,
+    /// Who gets the blame, and the XP, for the harm it does. Saved with
+    /// `StatusEffects`, the statuses items and traps inflict have nobody yet.
+    #[serde(skip)]
+    pub inflicted_by: Option<Entity>,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, turns: i32) -> StatusEffect {
+        StatusEffect {
+            kind,
+            turns,
+            potency: kind.default_potency(),
+            inflicted_by: None,
+        }
+    }
+}
+
+/// The statuses a creature is under, ticked by `StatusEffectSystem`.
+#[derive(Component, Clone, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+/// `StatusEffects` as saved, with whoever inflicted them as markers.
+#[derive(Serialize, Deserialize)]
+pub struct StatusEffectsData<M> {
+    pub effects: Vec<(StatusEffect, Option<M>)>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for StatusEffects
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = StatusEffectsData<M>;
+    type Error = std::convert::Infallible;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        let effects = self
+            .effects
+            .iter()
+            .map(|effect| (effect.clone(), effect.inflicted_by.and_then(&mut ids)))
+            .collect();
+        Ok(StatusEffectsData { effects })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        let effects = data
+            .effects
+            .into_iter()
+            .map(|(effect, inflicted_by)| StatusEffect {
+                inflicted_by: inflicted_by.and_then(&mut ids),
+                ..effect
+            })
+            .collect();
+        Ok(StatusEffects { effects })
+    }
+}
+
+impl StatusEffects {
+    pub fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    /// Adds `effect` following the stacking rule of its kind.
+    pub fn add(&mut self, effect: StatusEffect) {
+        if let Some(opposite) = effect.kind.opposite() {
+            if self.has(opposite) {
+                self.effects.retain(|e| e.kind != opposite);
+                return;
+            }
+        }
+        match self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            None => self.effects.push(effect),
+            Some(current) => {
+                // whoever made it worse gets the blame from now on
+                current.inflicted_by = effect.inflicted_by.or(current.inflicted_by);
+                match effect.kind.stacking() {
+                    Stacking::Intensity => {
+                        current.potency += effect.potency;
+                        current.turns = i32::max(current.turns, effect.turns);
+                    }
+                    Stacking::Refresh => {
+                        current.potency = i32::max(current.potency, effect.potency);
+                        current.turns = i32::max(current.turns, effect.turns);
+                    }
+                    Stacking::Duration => current.turns += effect.turns,
+                }
+            }
+        }
+    }
+
+    /// Energy gained per tick at `speed`, once hasted or slowed.
+    pub fn speed(&self, speed: i32) -> i32 {
+        if self.has(StatusKind::Haste) {
+            speed * 2
+        } else if self.has(StatusKind::Slow) {
+            speed / 2
+        } else {
+            speed
+        }
+    }
+}
+
+/// Statuses an item puts on its targets.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct InflictsStatus {
+    pub effects: Vec<StatusEffect>,
 }
 
 #[derive(PartialEq, Serialize, Deserialize, Clone, Copy)]
-pub enum EquipmentSlot { Melee, Shield }
+pub enum EquipmentSlot {
+    Melee,
+    Ranged,
+    Shield,
+    Head,
+    Torso,
+    Legs,
+    Feet,
+    Hands,
+    Ring,
+    Amulet,
+}
+
+impl EquipmentSlot {
+    /// Every slot, in the order of the character sheet.
+    pub const ALL: [EquipmentSlot; 10] = [
+        EquipmentSlot::Melee,
+        EquipmentSlot::Ranged,
+        EquipmentSlot::Shield,
+        EquipmentSlot::Head,
+        EquipmentSlot::Torso,
+        EquipmentSlot::Legs,
+        EquipmentSlot::Feet,
+        EquipmentSlot::Hands,
+        EquipmentSlot::Ring,
+        EquipmentSlot::Amulet,
+    ];
+
+    /// How many items can be worn in the slot at once, one ring on each hand.
+    pub fn capacity(&self) -> usize {
+        match self {
+            EquipmentSlot::Ring => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Melee",
+            EquipmentSlot::Ranged => "Ranged",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Torso => "Torso",
+            EquipmentSlot::Legs => "Legs",
+            EquipmentSlot::Feet => "Feet",
+            EquipmentSlot::Hands => "Hands",
+            EquipmentSlot::Ring => "Ring",
+            EquipmentSlot::Amulet => "Amulet",
+        }
+    }
+}
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Equippable {
@@ -144,14 +525,59 @@ pub struct Equipped {
     pub slot: EquipmentSlot,
 }
 
-#[derive(Component, Clone, ConvertSaveload)]
-pub struct MeleePowerBonus {
-    pub power: i32
+/// Damage dice of a weapon. On a creature rather than an item, its natural attack.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct MeleeWeapon {
+    pub damage_n_dice: i32,
+    pub damage_die_type: i32,
+    pub damage_bonus: i32,
+    pub hit_bonus: i32,
 }
 
-#[derive(Component, Clone, ConvertSaveload)]
-pub struct DefenseBonus {
-    pub defense: i32
+/// A bow or a throwing weapon, which uses up the `ammo` item it names. Throwing
+/// weapons are their own ammunition.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct RangedWeapon {
+    pub range: i32,
+    pub damage_n_dice: i32,
+    pub damage_die_type: i32,
+    pub damage_bonus: i32,
+    pub hit_bonus: i32,
+    pub ammo: String,
+}
+
+/// A stack of arrows, darts... fired one at a time.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Ammunition {
+    pub count: i32,
+}
+
+/// Potions, scrolls... carried in a single backpack entry: picked up ones join
+/// the stack, and they are used and dropped one at a time.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Stackable {
+    pub count: i32,
+}
+
+/// Fire the equipped ranged weapon at a tile. Never saved, it is resolved in
+/// the turn it is made.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToShoot {
+    pub target: rltk::Point,
+}
+
+/// What an item adds to whoever has it equipped. Missing bonuses are 0.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EquipmentBonus {
+    /// Added to melee damage.
+    pub power: i32,
+    /// Added to armor class.
+    pub defense: i32,
+    pub max_hp: i32,
+    /// Added to the field of view range.
+    pub vision: i32,
+    pub speed: i32,
 }
 
 #[derive(Debug, Component, Clone, ConvertSaveload)]
@@ -161,21 +587,83 @@ pub struct WantsToRemoveItem {
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct ParticleLifetime {
-    pub lifetime_ms: f32
+    pub lifetime_ms: f32,
+    /// Hidden until then, so that particles can play one after the other.
+    pub delay_ms: f32,
 }
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct InflictsTeleportsSymetrically {}
 
-#[derive(Component, ConvertSaveload, Clone)]
-pub struct TeleportsSymetrically {
-    pub from: Entity,
+/// Sends whoever sets it off to a random spot of the level.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct TeleportsRandomly {}
+
+/// Not drawn until spotted, see `gamesystem::spots_hidden`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Hidden {}
+
+/// Fires its effects on whoever steps on its tile, see `TriggerSystem`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct EntryTrigger {}
+
+/// Removed once it has fired.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SingleActivation {}
+
+/// Closed, it blocks the way and the view. Bumping into it opens it, if it
+/// isn't locked or the bumper has a `Key`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Door {
+    pub open: bool,
+    pub locked: bool,
 }
 
+/// Nothing can be seen through the tile, see `Map::view_blocked`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct BlocksVisibility {}
+
+/// Unlocks one door, and is used up doing so.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Key {}
+
+/// The entity stepped onto a new tile this turn. Never saved, the triggers
+/// there fire in the same turn.
+#[derive(Component, Debug, Clone)]
+pub struct EntityMoved {}
+
 // =============================================================
 // Special component that exists to help serialize the game data
 // =============================================================
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
-    pub map : super::map::Map
+    pub map : super::map::Map,
+    pub seed : u64,
+    pub turns : i32,
+    pub permadeath : bool,
+    pub dungeon : super::dungeon::MasterDungeonMap,
+}
+#[cfg(test)]
+mod tests {
+    use super::{StatusEffect, StatusEffects, StatusKind};
+
+    #[test]
+    fn statuses_stack_by_kind() {
+        let mut statuses = StatusEffects::default();
+        statuses.add(StatusEffect::new(StatusKind::Poison, 5));
+        statuses.add(StatusEffect::new(StatusKind::Poison, 3));
+        let poison = statuses.get(StatusKind::Poison).unwrap();
+        assert_eq!((poison.turns, poison.potency), (5, 2));
+
+        statuses.add(StatusEffect::new(StatusKind::Confusion, 3));
+        statuses.add(StatusEffect::new(StatusKind::Confusion, 3));
+        assert_eq!(statuses.get(StatusKind::Confusion).unwrap().turns, 6);
+
+        statuses.add(StatusEffect::new(StatusKind::Haste, 4));
+        assert_eq!(statuses.speed(10), 20);
+        // slow and haste cancel out
+        statuses.add(StatusEffect::new(StatusKind::Slow, 4));
+        assert!(!statuses.has(StatusKind::Haste) && !statuses.has(StatusKind::Slow));
+        assert_eq!(statuses.speed(10), 10);
+    }
 }
@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use crate::{gamelog::{GameLog, LogCategory, Logger}, AutoWalk, RunState};
 
-use crate::{components::{TeleportsSymetrically, Viewshed}, gamelog::GameLog, RunState};
-
-use super::{CombatStats, Map, Name, Player, Position, SufferDamage};
+use super::{
+    gamesystem::attr_bonus, Attributes, CombatStats, Equipped, Experience, InBackpack, Map, Name,
+    Player, Position, Skills, SufferDamage,
+};
 use rltk::console;
 use specs::prelude::*;
 
+/// XP a kill is worth for each level of the victim.
+pub const XP_PER_VICTIM_LEVEL: i32 = 100;
+/// XP needed per level to reach the next one.
+pub const XP_PER_LEVEL: i32 = 1000;
+
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
@@ -15,13 +21,48 @@ impl<'a> System<'a> for DamageSystem {
         ReadStorage<'a, Position>,
         WriteExpect<'a, Map>,
         Entities<'a>,
+        WriteStorage<'a, Experience>,
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, Skills>,
+        ReadStorage<'a, Attributes>,
+        WriteExpect<'a, AutoWalk>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
-
+        let (
+            mut stats,
+            mut damage,
+            positions,
+            mut map,
+            entities,
+            mut experience,
+            players,
+            mut log,
+            mut skills,
+            attributes,
+            mut auto_walk,
+        ) = data;
+
+        let mut kills: Vec<(Entity, i32)> = Vec::new();
         for (stats, damage, entity) in (&mut stats, &damage, &entities).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+            let mut killer = None;
+            // getting hurt stops the player walking by themselves
+            if players.contains(entity) && damage.amount.iter().any(|(amount, _)| *amount > 0) {
+                *auto_walk = AutoWalk::Off;
+            }
+            for (amount, from) in damage.amount.iter() {
+                let was_alive = stats.hp > 0;
+                stats.hp -= amount;
+                if was_alive && stats.hp <= 0 {
+                    killer = Some(*from);
+                }
+            }
+            // starving to death earns nothing
+            if let Some(killer) = killer.filter(|killer| *killer != entity) {
+                let level = experience.get(entity).map_or(1, |xp| xp.level);
+                kills.push((killer, level));
+            }
             let pos = positions.get(entity);
             if let Some(pos) = pos {
                 let idx = map.get_index_at(pos.x, pos.y);
@@ -30,61 +71,32 @@ impl<'a> System<'a> for DamageSystem {
         }
 
         damage.clear();
-    }
-}
 
-pub struct TeleportedSymSystem {}
-impl<'a> System<'a> for TeleportedSymSystem {
-    type SystemData = (
-        WriteStorage<'a, Position>,
-        WriteStorage<'a, TeleportsSymetrically>,
-        Entities<'a>,
-        WriteExpect<'a, Map>,
-        WriteStorage<'a, Viewshed>,
-    );
-
-    fn run(&mut self, data: Self::SystemData) {
-        let (mut positions, mut tp_syms, entities, mut map, mut viewsheds) = data;
-        
-        let mut middle_positions: HashMap<Entity, Position> = HashMap::new();
-        {
-            for (entity, tp_sym) in (&entities, &tp_syms).join() {
-                let mid_pos = positions.get(tp_sym.from).unwrap();
-                middle_positions.insert(entity, mid_pos.clone());
+        for (killer, victim_level) in kills {
+            if let (Some(xp), Some(stats)) = (experience.get_mut(killer), stats.get_mut(killer)) {
+                xp.xp += victim_level * XP_PER_VICTIM_LEVEL;
+                while xp.xp >= xp.level * XP_PER_LEVEL {
+                    xp.xp -= xp.level * XP_PER_LEVEL;
+                    xp.level += 1;
+                    let fitness = attributes.get(killer).map_or(0, |a| attr_bonus(a.fitness));
+                    stats.max_hp += i32::max(1, 10 + fitness);
+                    stats.hp = stats.max_hp;
+                    if let Some(skills) = skills.get_mut(killer) {
+                        skills.melee += 1;
+                        skills.defense += 1;
+                        skills.ranged += 1;
+                    }
+                    if players.get(killer).is_some() {
+                        Logger::new(LogCategory::System)
+                            .color(
+                                rltk::RGB::named(rltk::MAGENTA),
+                                format!("Congratulations, you are now level {}!", xp.level),
+                            )
+                            .log(&mut log);
+                    }
+                }
             }
         }
-
-        for (entity, mid_pos) in middle_positions {
-            let pos = positions.get_mut(entity).unwrap();
-            let vs = viewsheds.get_mut(entity).unwrap();
-            let delta_x = pos.x - mid_pos.x;
-            let delta_y = pos.y - mid_pos.y;
-
-            let new_x = mid_pos.x - delta_x;
-            let new_y = mid_pos.y - delta_y;
-            pos.x = new_x;
-            pos.y = new_y;
-            let idx = map.get_index_at(new_x, new_y);
-            map.blocked[idx] = true;
-            map.tile_content[idx].push(entity);
-            vs.dirty = true;
-
-        }
-        tp_syms.clear();
-
-
-
-        /*
-        for (tp_sym, entity) in (&mut tp_syms, &entities).join() {
-            let from = positions.get(tp_sym.from).unwrap().clone();
-
-            let entity_pos = positions.get_mut(entity).unwrap();
-
-            entity_pos.x = from.x;
-            entity_pos.y = from.y + 4;
-        }
-        tp_syms.clear();
-        */
     }
 }
 
@@ -105,7 +117,10 @@ pub fn delete_the_dead(ecs: &mut World) {
                     None => {
                         let victim_name = names.get(entity);
                         if let Some(victim_name) = victim_name {
-                            log.entries.push(format!("{} is dead", &victim_name.name));
+                            Logger::new(LogCategory::Combat)
+                                .name(&victim_name.name)
+                                .append("is dead")
+                                .log(&mut log);
                         }
                         deads.push(entity);
                     }
@@ -117,8 +132,37 @@ pub fn delete_the_dead(ecs: &mut World) {
             }
         }
     }
+    drop_belongings(ecs, &deads);
     for victim in deads {
         ecs.delete_entity(victim)
             .expect("Unable to delete dead enitity");
     }
 }
+
+/// Leaves what the dead carried or wore where they fell.
+fn drop_belongings(ecs: &mut World, deads: &[Entity]) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut equipped = ecs.write_storage::<Equipped>();
+
+    let mut dropped = Vec::new();
+    for (item, in_backpack, equipped_by) in (&entities, backpack.maybe(), equipped.maybe()).join() {
+        let owner = in_backpack
+            .map(|b| b.owner)
+            .or(equipped_by.map(|e| e.owner));
+        if let Some(owner) = owner.filter(|owner| deads.contains(owner)) {
+            dropped.push((item, owner));
+        }
+    }
+
+    for (item, owner) in dropped {
+        backpack.remove(item);
+        equipped.remove(item);
+        if let Some(pos) = positions.get(owner).cloned() {
+            positions
+                .insert(item, pos)
+                .expect("Unable to insert position");
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::components::{MyTurn, OtherLevelPosition, Position};
+use super::map::{Map, MAPCOUNT};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashMap;
+
+/// The levels the player has left, kept as they were so that going back finds
+/// them unchanged. Their entities stay in the world, with an
+/// `OtherLevelPosition` instead of a `Position`.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct MasterDungeonMap {
+    maps: HashMap<i32, Map>,
+}
+
+impl MasterDungeonMap {
+    pub fn store_map(&mut self, map: &Map) {
+        self.maps.insert(map.depth, map.clone());
+    }
+
+    /// Removes a level from the store, ready to become the current map.
+    pub fn take_map(&mut self, depth: i32) -> Option<Map> {
+        self.maps.remove(&depth).map(|mut map| {
+            map.tile_content = vec![Vec::new(); MAPCOUNT];
+            map.visible_tiles = vec![false; MAPCOUNT];
+            map
+        })
+    }
+}
+
+/// Moves everything on the current level but the player out of the world's way.
+/// Turns still to be taken there are lost, time stands still on other levels.
+pub fn freeze_level_entities(ecs: &mut World) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut my_turn = ecs.write_storage::<MyTurn>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let player_entity = ecs.fetch::<Entity>();
+    let depth = ecs.fetch::<Map>().depth;
+
+    let mut frozen = Vec::new();
+    for (entity, pos) in (&entities, &positions).join() {
+        if entity != *player_entity {
+            other_level_positions
+                .insert(
+                    entity,
+                    OtherLevelPosition {
+                        x: pos.x,
+                        y: pos.y,
+                        depth,
+                    },
+                )
+                .expect("Unable to insert other level position");
+            frozen.push(entity);
+        }
+    }
+
+    for entity in frozen {
+        positions.remove(entity);
+        my_turn.remove(entity);
+    }
+}
+
+/// Brings back the entities that were left on the current level.
+pub fn thaw_level_entities(ecs: &mut World) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let depth = ecs.fetch::<Map>().depth;
+
+    let mut thawed = Vec::new();
+    for (entity, pos) in (&entities, &other_level_positions).join() {
+        if pos.depth == depth {
+            positions
+                .insert(entity, Position { x: pos.x, y: pos.y })
+                .expect("Unable to insert position");
+            thawed.push(entity);
+        }
+    }
+
+    for entity in thawed {
+        other_level_positions.remove(entity);
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::VecDeque;
+
+use crate::{
+    components::{
+        AreaOfEffect, CombatStats, Consumable, HungerClock, HungerState, InflictsDamage,
+        InflictsStatus, InflictsTeleportsSymetrically, Name, Position, ProvidesFood,
+        ProvidesHealing, SingleActivation, Stackable, StatusEffect, StatusEffects, StatusKind,
+        SufferDamage, TeleportsRandomly, Viewshed,
+    },
+    gamelog::{GameLog, LogCategory, Logger},
+    hunger_system::WELL_FED_TURNS,
+    map::{Map, TileType},
+    particle_system::ParticleBuilder,
+};
+use rltk::{Point, RGB};
+use specs::prelude::*;
+
+/// What an effect does to each of its targets.
+#[derive(Clone)]
+pub enum EffectType {
+    Damage {
+        amount: i32,
+    },
+    Healing {
+        amount: i32,
+    },
+    Status {
+        effect: StatusEffect,
+    },
+    /// Mirrors the target's position through the creator's.
+    TeleportSymmetric,
+    /// Anywhere free on the level.
+    TeleportRandom,
+    WellFed,
+    Particle {
+        glyph: rltk::FontCharType,
+        fg: RGB,
+        bg: RGB,
+        lifetime: f32,
+    },
+    /// Everything the item does, turned into the effects above.
+    ItemUse {
+        item: Entity,
+    },
+    /// Everything a trap does to whoever set it off.
+    TriggerFire {
+        trigger: Entity,
+    },
+}
+
+#[derive(Clone)]
+pub enum Targets {
+    Single { target: Entity },
+    Tile { tile_idx: usize },
+    Tiles { tiles: Vec<usize> },
+}
+
+struct EffectRequest {
+    creator: Option<Entity>,
+    effect_type: EffectType,
+    targets: Targets,
+}
+
+/// Effects waiting for `run_effects_queue`. Items, traps, spells and attacks
+/// push what they do here rather than applying it themselves.
+#[derive(Default)]
+pub struct EffectQueue {
+    requests: VecDeque<EffectRequest>,
+}
+
+impl EffectQueue {
+    pub fn push(&mut self, creator: Option<Entity>, effect_type: EffectType, targets: Targets) {
+        self.requests.push_back(EffectRequest {
+            creator,
+            effect_type,
+            targets,
+        });
+    }
+}
+
+/// Applies every queued effect, and those they queue in turn.
+pub fn run_effects_queue(ecs: &mut World) {
+    loop {
+        let request = ecs.write_resource::<EffectQueue>().requests.pop_front();
+        match request {
+            Some(request) => apply(ecs, request),
+            None => break,
+        }
+    }
+}
+
+fn apply(ecs: &mut World, request: EffectRequest) {
+    match &request.effect_type {
+        EffectType::ItemUse { item } => item_trigger(ecs, request.creator, *item, &request.targets),
+        EffectType::TriggerFire { trigger } => trigger_fire(ecs, *trigger, &request.targets),
+        EffectType::Particle {
+            glyph,
+            fg,
+            bg,
+            lifetime,
+        } => {
+            let map = ecs.fetch::<Map>();
+            let mut particle_builder = ecs.write_resource::<ParticleBuilder>();
+            for idx in tiles_of(ecs, &request.targets) {
+                let (x, y) = map.get_xy_from_idx(idx);
+                particle_builder.request(x, y, *fg, *bg, *glyph, *lifetime);
+            }
+        }
+        effect_type => {
+            for target in entities_in(ecs, &request.targets) {
+                affect_entity(ecs, request.creator, effect_type, target);
+            }
+        }
+    }
+}
+
+/// The entities standing in the targeted tiles.
+fn entities_in(ecs: &World, targets: &Targets) -> Vec<Entity> {
+    let map = ecs.fetch::<Map>();
+    match targets {
+        Targets::Single { target } => vec![*target],
+        Targets::Tile { tile_idx } => map.tile_content[*tile_idx].clone(),
+        Targets::Tiles { tiles } => tiles
+            .iter()
+            .flat_map(|idx| map.tile_content[*idx].iter().copied())
+            .collect(),
+    }
+}
+
+fn tiles_of(ecs: &World, targets: &Targets) -> Vec<usize> {
+    match targets {
+        Targets::Single { target } => {
+            let map = ecs.fetch::<Map>();
+            ecs.read_storage::<Position>()
+                .get(*target)
+                .map(|pos| map.get_index_at(pos.x, pos.y))
+                .into_iter()
+                .collect()
+        }
+        Targets::Tile { tile_idx } => vec![*tile_idx],
+        Targets::Tiles { tiles } => tiles.clone(),
+    }
+}
+
+/// Only creatures take damage, heal or suffer statuses.
+fn affect_entity(
+    ecs: &mut World,
+    creator: Option<Entity>,
+    effect_type: &EffectType,
+    target: Entity,
+) {
+    let is_creature = ecs.read_storage::<CombatStats>().contains(target);
+    match effect_type {
+        EffectType::Damage { amount } if is_creature => {
+            SufferDamage::new_damage(
+                &mut ecs.write_storage::<SufferDamage>(),
+                target,
+                *amount,
+                creator.unwrap_or(target),
+            );
+        }
+        EffectType::Healing { amount } => {
+            if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(target) {
+                stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+            }
+        }
+        EffectType::Status { effect } if is_creature => {
+            let mut statuses = ecs.write_storage::<StatusEffects>();
+            if let Ok(entry) = statuses.entry(target) {
+                entry
+                    .or_insert_with(StatusEffects::default)
+                    .add(StatusEffect {
+                        inflicted_by: creator,
+                        ..effect.clone()
+                    });
+            }
+            if effect.kind == StatusKind::Blindness {
+                if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+                    viewshed.dirty = true;
+                }
+            }
+        }
+        EffectType::TeleportSymmetric => {
+            if let Some(creator) = creator {
+                teleport_symmetric(ecs, creator, target);
+            }
+        }
+        EffectType::TeleportRandom => teleport_random(ecs, target),
+        EffectType::WellFed => {
+            if let Some(clock) = ecs.write_storage::<HungerClock>().get_mut(target) {
+                clock.state = HungerState::WellFed;
+                clock.duration = WELL_FED_TURNS;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Moves `target` to the other side of `center`, if there is room there.
+fn teleport_symmetric(ecs: &mut World, center: Entity, target: Entity) {
+    let destination = {
+        let positions = ecs.read_storage::<Position>();
+        let (Some(center), Some(pos)) = (positions.get(center), positions.get(target)) else {
+            return;
+        };
+        let map = ecs.fetch::<Map>();
+        let (x, y) = (2 * center.x - pos.x, 2 * center.y - pos.y);
+        if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 {
+            return;
+        }
+        let idx = map.get_index_at(x, y);
+        if map.blocked[idx] {
+            return;
+        }
+        idx
+    };
+    place_entity(ecs, target, destination);
+}
+
+/// Moves `target` to a random free floor tile of the level.
+fn teleport_random(ecs: &mut World, target: Entity) {
+    let destination = {
+        let map = ecs.fetch::<Map>();
+        let free: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| map.tiles[*idx] == TileType::Floor && !map.blocked[*idx])
+            .collect();
+        if free.is_empty() {
+            return;
+        }
+        let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+        free[rng.range(0, free.len())]
+    };
+    place_entity(ecs, target, destination);
+}
+
+/// Puts `target` on the tile `idx`, keeping the map and the player's position
+/// up to date.
+fn place_entity(ecs: &mut World, target: Entity, idx: usize) {
+    let mut positions = ecs.write_storage::<Position>();
+    let Some(pos) = positions.get_mut(target) else {
+        return;
+    };
+    let mut map = ecs.write_resource::<Map>();
+    let old_idx = map.get_index_at(pos.x, pos.y);
+    map.blocked[old_idx] = false;
+    map.blocked[idx] = true;
+    (pos.x, pos.y) = map.get_xy_from_idx(idx);
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+        viewshed.dirty = true;
+    }
+    if target == *ecs.fetch::<Entity>() {
+        *ecs.write_resource::<Point>() = Point::new(pos.x, pos.y);
+    }
+}
+
+/// Queues what `item` does to the targets and logs it for the player. A
+/// consumable is used up once it had an effect.
+fn item_trigger(ecs: &mut World, creator: Option<Entity>, item: Entity, targets: &Targets) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let by_player = creator == Some(player_entity);
+    let victims = entities_in(ecs, targets);
+    let names = ecs.read_storage::<Name>();
+    let item_name = names.get(item).map_or("item", |name| name.name.as_str());
+    let name_of = |entity: &Entity| names.get(*entity).map_or("someone", |n| n.name.as_str());
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let mut queue = ecs.write_resource::<EffectQueue>();
+    let mut log = ecs.write_resource::<GameLog>();
+    let mut used_item = false;
+
+    if ecs.read_storage::<AreaOfEffect>().contains(item) {
+        queue.push(
+            creator,
+            EffectType::Particle {
+                glyph: rltk::to_cp437('░'),
+                fg: RGB::named(rltk::ORANGE),
+                bg: RGB::named(rltk::BLACK),
+                lifetime: 200.0,
+            },
+            targets.clone(),
+        );
+    }
+
+    if ecs.read_storage::<ProvidesFood>().contains(item) {
+        let hunger_clocks = ecs.read_storage::<HungerClock>();
+        for victim in victims.iter().filter(|v| hunger_clocks.contains(**v)) {
+            queue.push(
+                creator,
+                EffectType::WellFed,
+                Targets::Single { target: *victim },
+            );
+            used_item = true;
+            if *victim == player_entity {
+                Logger::new(LogCategory::Item)
+                    .append("You eat the")
+                    .item(item_name)
+                    .append(".")
+                    .log(&mut log);
+            }
+        }
+    }
+
+    if let Some(healer) = ecs.read_storage::<ProvidesHealing>().get(item) {
+        for victim in victims.iter().filter(|v| combat_stats.contains(**v)) {
+            let target = Targets::Single { target: *victim };
+            queue.push(
+                creator,
+                EffectType::Healing {
+                    amount: healer.heal_amount,
+                },
+                target.clone(),
+            );
+            queue.push(creator, particle('♥', rltk::GREEN), target);
+            used_item = true;
+            if by_player {
+                Logger::new(LogCategory::Item)
+                    .append("You use")
+                    .item(item_name)
+                    .append(", healing")
+                    .healing(healer.heal_amount)
+                    .append("hp.")
+                    .log(&mut log);
+            }
+        }
+    }
+
+    if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(item) {
+        for victim in victims.iter().filter(|v| combat_stats.contains(**v)) {
+            let target = Targets::Single { target: *victim };
+            queue.push(
+                creator,
+                EffectType::Damage {
+                    amount: damage.damage,
+                },
+                target.clone(),
+            );
+            if by_player {
+                queue.push(creator, particle('‼', rltk::RED), target);
+                Logger::new(LogCategory::Combat)
+                    .append("You use")
+                    .item(item_name)
+                    .append("on")
+                    .name(name_of(victim))
+                    .append(", inflicting")
+                    .damage(damage.damage)
+                    .append("hp.")
+                    .log(&mut log);
+            }
+        }
+        used_item = true;
+    }
+
+    if ecs
+        .read_storage::<InflictsTeleportsSymetrically>()
+        .contains(item)
+    {
+        for victim in victims.iter() {
+            queue.push(
+                creator,
+                EffectType::TeleportSymmetric,
+                Targets::Single { target: *victim },
+            );
+            if by_player {
+                Logger::new(LogCategory::Item)
+                    .append("You use")
+                    .item(item_name)
+                    .append("on")
+                    .name(name_of(victim))
+                    .append(", teleporting them.")
+                    .log(&mut log);
+            }
+        }
+        used_item = true;
+    }
+
+    if let Some(inflicts) = ecs.read_storage::<InflictsStatus>().get(item) {
+        let labels: Vec<&str> = inflicts.effects.iter().map(|e| e.kind.label()).collect();
+        let labels = labels.join(" and ");
+        for victim in victims.iter().filter(|v| combat_stats.contains(**v)) {
+            let target = Targets::Single { target: *victim };
+            for effect in inflicts.effects.iter() {
+                queue.push(
+                    creator,
+                    EffectType::Status {
+                        effect: effect.clone(),
+                    },
+                    target.clone(),
+                );
+            }
+            let (glyph, color) = inflicts.effects[0].kind.icon();
+            queue.push(creator, particle(glyph, color), target);
+            used_item = true;
+
+            if *victim == player_entity {
+                Logger::new(LogCategory::Item)
+                    .append("You use")
+                    .item(item_name)
+                    .append(format!(", you are {}.", labels))
+                    .log(&mut log);
+            } else if by_player {
+                Logger::new(LogCategory::Item)
+                    .append("You use")
+                    .item(item_name)
+                    .append("on")
+                    .name(name_of(victim))
+                    .append(format!(", leaving them {}.", labels))
+                    .log(&mut log);
+            }
+        }
+    }
+
+    if used_item && ecs.read_storage::<Consumable>().contains(item) {
+        // a stack is used up one item at a time
+        if let Some(stack) = ecs
+            .write_storage::<Stackable>()
+            .get_mut(item)
+            .filter(|stack| stack.count > 1)
+        {
+            stack.count -= 1;
+            return;
+        }
+        ecs.entities()
+            .delete(item)
+            .expect("Delete consumable failed");
+    }
+}
+
+/// Queues what a trap does to whoever set it off. A single use trap is gone
+/// afterwards.
+fn trigger_fire(ecs: &mut World, trigger: Entity, targets: &Targets) {
+    let victims = entities_in(ecs, targets);
+    {
+        let map = ecs.fetch::<Map>();
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let mut queue = ecs.write_resource::<EffectQueue>();
+        let mut log = ecs.write_resource::<GameLog>();
+        let trap_name = names.get(trigger).map_or("trap", |name| name.name.as_str());
+
+        for victim in victims.iter().filter(|v| combat_stats.contains(**v)) {
+            let target = Targets::Single { target: *victim };
+            let seen = positions
+                .get(*victim)
+                .is_some_and(|pos| map.visible_tiles[map.get_index_at(pos.x, pos.y)]);
+
+            if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(trigger) {
+                queue.push(
+                    Some(trigger),
+                    EffectType::Damage {
+                        amount: damage.damage,
+                    },
+                    target.clone(),
+                );
+                queue.push(Some(trigger), particle('‼', rltk::RED), target.clone());
+                if seen {
+                    Logger::new(LogCategory::Combat)
+                        .item(trap_name)
+                        .append("hurts")
+                        .name(names.get(*victim).map_or("someone", |n| n.name.as_str()))
+                        .append(", for")
+                        .damage(damage.damage)
+                        .append("hp")
+                        .log(&mut log);
+                }
+            }
+
+            if let Some(inflicts) = ecs.read_storage::<InflictsStatus>().get(trigger) {
+                for effect in inflicts.effects.iter() {
+                    queue.push(
+                        Some(trigger),
+                        EffectType::Status {
+                            effect: effect.clone(),
+                        },
+                        target.clone(),
+                    );
+                }
+            }
+
+            if ecs.read_storage::<TeleportsRandomly>().contains(trigger) {
+                queue.push(Some(trigger), EffectType::TeleportRandom, target);
+            }
+        }
+    }
+
+    if ecs.read_storage::<SingleActivation>().contains(trigger) {
+        ecs.entities()
+            .delete(trigger)
+            .expect("Delete used trap failed");
+    }
+}
+
+/// A short flash over the target.
+pub fn particle(glyph: char, color: (u8, u8, u8)) -> EffectType {
+    EffectType::Particle {
+        glyph: rltk::to_cp437(glyph),
+        fg: RGB::named(color),
+        bg: RGB::named(rltk::BLACK),
+        lifetime: 200.0,
+    }
+}
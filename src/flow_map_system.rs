@@ -0,0 +1,139 @@
+use super::{BlocksTile, CombatStats, Map, Position, RunState, TurnCounter};
+use crate::map::{MAPHEIGHT, MAPWIDTH};
+use rltk::{BaseMap, DijkstraMap, Point};
+use specs::prelude::*;
+
+/// Further than any walk across the map, tiles that can't be reached stay there.
+const MAX_DEPTH: f32 = 2000.0;
+/// The flee map is the flow map turned upside down and scaled by this, so that
+/// running a long way around the player beats backing into the nearest corner.
+const FLEE_FACTOR: f32 = -1.2;
+
+/// How far every tile is from the player, walking, worked out once per turn
+/// and shared by all the monsters going for them instead of each running its
+/// own search. The flee map, leading away from the player, is only worked out
+/// the first time a monster runs from them in the turn.
+///
+/// Creatures are walked through when the flow map is built, since most of
+/// them will have moved by the time it is used; the step helpers only pick
+/// tiles free at that moment.
+pub struct PlayerFlowMap {
+    /// The turn, depth and player position the maps were built for.
+    built_for: Option<(i32, i32, Point)>,
+    toward: DijkstraMap,
+    away: Option<DijkstraMap>,
+}
+
+impl Default for PlayerFlowMap {
+    fn default() -> PlayerFlowMap {
+        PlayerFlowMap {
+            built_for: None,
+            toward: DijkstraMap::new_empty(MAPWIDTH, MAPHEIGHT, MAX_DEPTH),
+            away: None,
+        }
+    }
+}
+
+impl PlayerFlowMap {
+    /// The free neighbouring tile of `idx` on the way to the player, if the
+    /// player can be reached from there. Never the player's own tile.
+    pub fn step_toward(&self, map: &Map, idx: usize) -> Option<usize> {
+        lowest_exit(&self.toward, map, idx, |exit| self.toward.map[exit] > 0.0)
+    }
+
+    /// The free neighbouring tile of `idx` on the way away from the player.
+    /// The flee map may lead around the player, but never a step closer.
+    pub fn step_away(&mut self, map: &Map, idx: usize) -> Option<usize> {
+        let away = self.away.get_or_insert_with(|| flee_map(&self.toward, map));
+        lowest_exit(away, map, idx, |exit| {
+            self.toward.map[exit] >= self.toward.map[idx]
+        })
+    }
+}
+
+/// The exit of `idx` allowed by `allowed` going down `flow` the fastest, if
+/// it goes down at all.
+fn lowest_exit(
+    flow: &DijkstraMap,
+    map: &Map,
+    idx: usize,
+    allowed: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    map.get_available_exits(idx)
+        .into_iter()
+        .map(|(exit, _)| exit)
+        .filter(|exit| allowed(*exit) && flow.map[*exit] < flow.map[idx])
+        .min_by(|a, b| flow.map[*a].total_cmp(&flow.map[*b]))
+}
+
+/// `toward` scaled by `FLEE_FACTOR`, then smoothed out so that the way to the
+/// furthest places runs downhill.
+fn flee_map(toward: &DijkstraMap, map: &Map) -> DijkstraMap {
+    let mut starts: Vec<(usize, f32)> = toward
+        .map
+        .iter()
+        .enumerate()
+        .filter(|(_, distance)| **distance < MAX_DEPTH)
+        .map(|(idx, distance)| (idx, distance * FLEE_FACTOR))
+        .collect();
+    // lowest first, fewer tiles get lowered more than once
+    starts.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut away = DijkstraMap::new_empty(MAPWIDTH, MAPHEIGHT, MAX_DEPTH);
+    for (idx, depth) in starts.iter() {
+        away.map[*idx] = *depth;
+    }
+    DijkstraMap::build_weighted(&mut away, &starts, map);
+    away
+}
+
+/// Rebuilds the `PlayerFlowMap` before the monsters move, when the turn, the
+/// level or the player's position changed since it was last built.
+pub struct FlowMapSystem {}
+
+impl<'a> System<'a> for FlowMapSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, PlayerFlowMap>,
+        ReadExpect<'a, Point>,
+        ReadExpect<'a, TurnCounter>,
+        ReadExpect<'a, RunState>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, CombatStats>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, mut flow, player_pos, turns, runstate, positions, blockers, combat_stats) =
+            data;
+
+        if *runstate != RunState::Ticking {
+            return;
+        }
+        let key = (turns.turns, map.depth, *player_pos);
+        if flow.built_for == Some(key) {
+            return;
+        }
+        flow.built_for = Some(key);
+        flow.away = None;
+
+        let creatures: Vec<usize> = (&positions, &blockers, &combat_stats)
+            .join()
+            .map(|(pos, _, _)| map.get_index_at(pos.x, pos.y))
+            .filter(|idx| map.blocked[*idx])
+            .collect();
+        for idx in creatures.iter() {
+            map.blocked[*idx] = false;
+        }
+
+        let start = map.get_index_at(player_pos.x, player_pos.y);
+        DijkstraMap::clear(&mut flow.toward);
+        DijkstraMap::build(&mut flow.toward, &[start], &*map);
+        // the player's own tile is where everything flows to
+        flow.toward.map[start] = 0.0;
+
+        for idx in creatures {
+            map.blocked[idx] = true;
+        }
+    }
+}
@@ -1,3 +1,130 @@
+use rltk::RGB;
+use std::collections::VecDeque;
+
+/// Oldest entries are dropped past this many.
+pub const MAX_LOG_ENTRIES: usize = 1000;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LogCategory {
+    Combat,
+    Item,
+    System,
+}
+
+impl LogCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogCategory::Combat => "Combat",
+            LogCategory::Item => "Item",
+            LogCategory::System => "System",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LogFragment {
+    pub color: RGB,
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub struct LogEntry {
+    pub category: LogCategory,
+    pub turn: i32,
+    pub fragments: Vec<LogFragment>,
+}
+
+#[derive(Default)]
 pub struct GameLog {
-    pub entries: Vec<String>,
+    entries: VecDeque<LogEntry>,
+    /// Stamped on new entries, kept in step with the `TurnCounter`.
+    pub turn: i32,
+}
+
+impl GameLog {
+    /// Oldest first.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> + ExactSizeIterator {
+        self.entries.iter()
+    }
+
+    fn push(&mut self, category: LogCategory, fragments: Vec<LogFragment>) {
+        if self.entries.len() == MAX_LOG_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            category,
+            turn: self.turn,
+            fragments,
+        });
+    }
+}
+
+/// Builds one log entry out of colored fragments:
+/// `Logger::new(LogCategory::Combat).name("Goblin").append("hurts").damage(4).log(&mut log)`.
+/// Fragments are separated by spaces.
+pub struct Logger {
+    category: LogCategory,
+    fragments: Vec<LogFragment>,
+}
+
+impl Logger {
+    pub fn new(category: LogCategory) -> Logger {
+        Logger {
+            category,
+            fragments: Vec::new(),
+        }
+    }
+
+    pub fn color<T: ToString>(mut self, color: RGB, text: T) -> Logger {
+        let mut text = text.to_string();
+        if !self.fragments.is_empty() && !text.starts_with(|c: char| c.is_ascii_punctuation()) {
+            text.insert(0, ' ');
+        }
+        self.fragments.push(LogFragment { color, text });
+        self
+    }
+
+    pub fn append<T: ToString>(self, text: T) -> Logger {
+        self.color(RGB::named(rltk::WHITE), text)
+    }
+
+    /// A creature's name.
+    pub fn name<T: ToString>(self, text: T) -> Logger {
+        self.color(RGB::named(rltk::YELLOW), text)
+    }
+
+    pub fn item<T: ToString>(self, text: T) -> Logger {
+        self.color(RGB::named(rltk::CYAN), text)
+    }
+
+    pub fn damage(self, amount: i32) -> Logger {
+        self.color(RGB::named(rltk::RED), amount)
+    }
+
+    pub fn healing(self, amount: i32) -> Logger {
+        self.color(RGB::named(rltk::GREEN), amount)
+    }
+
+    pub fn log(self, log: &mut GameLog) {
+        log.push(self.category, self.fragments);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::{GameLog, LogCategory, Logger, MAX_LOG_ENTRIES};
+    use crate::headless::{
+        fixtures::{text, SEED},
+        HeadlessGame,
+    };
+
+    #[test]
+    fn the_log_is_capped() {
+        let mut game = HeadlessGame::new(SEED);
+        let mut log = game.world_mut().fetch_mut::<GameLog>();
+        for i in 0..MAX_LOG_ENTRIES + 10 {
+            Logger::new(LogCategory::System).append(i).log(&mut log);
+        }
+        assert_eq!(log.entries().len(), MAX_LOG_ENTRIES);
+        assert_eq!(log.entries().next().map(text).unwrap(), "10");
+    }
 }
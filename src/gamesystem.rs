@@ -0,0 +1,133 @@
+use super::components::{
+    Ammunition, Attributes, EquipmentBonus, Equipped, InBackpack, Item, Skills, Stackable,
+};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::ops::Deref;
+
+/// What an attribute adds to rolls: +1 for every two points above 10, and as
+/// much taken away below.
+pub fn attr_bonus(value: i32) -> i32 {
+    (value - 10) / 2
+}
+
+/// The bonuses of everything `owner` has equipped, added up.
+pub fn equipment_bonus<E, B>(
+    owner: Entity,
+    equipped: &Storage<Equipped, E>,
+    bonuses: &Storage<EquipmentBonus, B>,
+) -> EquipmentBonus
+where
+    E: Deref<Target = MaskedStorage<Equipped>>,
+    B: Deref<Target = MaskedStorage<EquipmentBonus>>,
+{
+    let mut total = EquipmentBonus::default();
+    for (equipped_by, bonus) in (equipped, bonuses).join() {
+        if equipped_by.owner == owner {
+            total.power += bonus.power;
+            total.defense += bonus.defense;
+            total.max_hp += bonus.max_hp;
+            total.vision += bonus.vision;
+            total.speed += bonus.speed;
+        }
+    }
+    total
+}
+
+/// The most `might` lets a creature carry before slowing down, in kilograms.
+pub fn carry_capacity(might: i32) -> f32 {
+    15.0 + 2.0 * might as f32
+}
+
+/// The weight of everything `owner` carries, in the backpack or equipped, in
+/// kilograms. Each item of a stack counts.
+pub fn carried_weight(
+    owner: Entity,
+    items: &ReadStorage<Item>,
+    backpack: &ReadStorage<InBackpack>,
+    equipped: &ReadStorage<Equipped>,
+    stacks: &ReadStorage<Stackable>,
+    ammunition: &ReadStorage<Ammunition>,
+) -> f32 {
+    (
+        items,
+        backpack.maybe(),
+        equipped.maybe(),
+        stacks.maybe(),
+        ammunition.maybe(),
+    )
+        .join()
+        .filter(|(_, packed, worn, _, _)| {
+            packed.is_some_and(|p| p.owner == owner) || worn.is_some_and(|e| e.owner == owner)
+        })
+        .map(|(item, _, _, stack, ammo)| {
+            let count = stack.map_or(ammo.map_or(1, |a| a.count), |s| s.count);
+            item.weight * count as f32
+        })
+        .sum()
+}
+
+pub enum AttackRoll {
+    /// A natural 1, always a miss.
+    Fumble,
+    Miss,
+    Hit,
+    /// A natural 20, always a hit, with the damage dice rolled twice.
+    Critical,
+}
+
+/// A d20 plus `hit_bonus`, which has to beat `armor_class`.
+pub fn roll_to_hit(
+    rng: &mut rltk::RandomNumberGenerator,
+    hit_bonus: i32,
+    armor_class: i32,
+) -> AttackRoll {
+    match rng.roll_dice(1, 20) {
+        1 => AttackRoll::Fumble,
+        20 => AttackRoll::Critical,
+        natural_roll if natural_roll + hit_bonus > armor_class => AttackRoll::Hit,
+        _ => AttackRoll::Miss,
+    }
+}
+
+/// What a hit roll has to beat before quickness, skill and armor.
+pub const BASE_ARMOR_CLASS: i32 = 10;
+
+/// How hard `target` is to hit, with its quickness, its defense skill and its armor.
+pub fn armor_class(
+    target: Entity,
+    attributes: &ReadStorage<Attributes>,
+    skills: &ReadStorage<Skills>,
+    equipped: &ReadStorage<Equipped>,
+    bonuses: &ReadStorage<EquipmentBonus>,
+) -> i32 {
+    BASE_ARMOR_CLASS
+        + attributes
+            .get(target)
+            .map_or(0, |a| attr_bonus(a.quickness))
+        + skills.get(target).map_or(0, |s| s.defense)
+        + equipment_bonus(target, equipped, bonuses).defense
+}
+
+/// Rolls the damage dice, twice over on a critical, plus `bonus`. At least 1.
+pub fn roll_damage(
+    rng: &mut rltk::RandomNumberGenerator,
+    roll: &AttackRoll,
+    n_dice: i32,
+    die_type: i32,
+    bonus: i32,
+) -> i32 {
+    let n_dice = match roll {
+        AttackRoll::Critical => n_dice * 2,
+        _ => n_dice,
+    };
+    i32::max(1, rng.roll_dice(n_dice, die_type) + bonus)
+}
+
+/// What a perception roll has to reach to spot something hidden.
+pub const SPOT_DIFFICULTY: i32 = 20;
+
+/// A d20 plus the intelligence bonus and `bonus`, against `SPOT_DIFFICULTY`.
+pub fn spots_hidden(rng: &mut rltk::RandomNumberGenerator, intelligence: i32, bonus: i32) -> bool {
+    rng.roll_dice(1, 20) + attr_bonus(intelligence) + bonus >= SPOT_DIFFICULTY
+}
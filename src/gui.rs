@@ -4,6 +4,7 @@ use specs::prelude::*;
 #[derive(PartialEq, Copy, Clone)]
 pub enum MainMenuSelection {
     NewGame,
+    NewPermadeathGame,
     LoadGame,
     Quit,
 }
@@ -21,6 +22,34 @@ pub enum ItemMenuResult {
     Selected,
 }
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotMenuMode {
+    Save,
+    Load,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotMenuResult {
+    NoSelection { selected: usize },
+    Selected { slot: usize },
+    Delete { slot: usize },
+    Cancel,
+}
+
+/// Shown under the main menu, for instance when a save can't be loaded.
+pub struct MenuMessage {
+    pub text: String,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LogViewResult {
+    NoResponse {
+        scroll: usize,
+        filter: Option<LogCategory>,
+    },
+    Close,
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum GameOverResult {
     NoSelection,
@@ -28,8 +57,15 @@ pub enum GameOverResult {
 }
 
 use crate::{
-    components::{CombatStats, Equipped, InBackpack, Player, Position, Viewshed},
-    gamelog::GameLog,
+    components::{
+        Ammunition, Attributes, CombatStats, EquipmentBonus, EquipmentSlot, Equipped, Experience,
+        Hidden, HungerClock, HungerState, InBackpack, Item, Player, Position, Skills, Stackable,
+        StatusEffects, Viewshed,
+    },
+    damage_system::XP_PER_LEVEL,
+    gamelog::{GameLog, LogCategory, LogEntry},
+    gamesystem::{carried_weight, carry_capacity, equipment_bonus},
+    saveload_system::{self, SaveSlots, SAVE_SLOTS},
     Aiming, Map, Name, RunState, State,
 };
 
@@ -66,13 +102,69 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         );
     }
 
-    let log = ecs.fetch::<GameLog>();
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
-        if y < 49 {
-            ctx.print(2, y, s)
+    // on the bottom wall of the map, just above the health bar
+    let hunger_clocks = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &hunger_clocks).join() {
+        let (text, color) = match clock.state {
+            HungerState::WellFed => ("Well Fed", rltk::GREEN),
+            HungerState::Normal => ("", rltk::WHITE),
+            HungerState::Hungry => ("Hungry", rltk::ORANGE),
+            HungerState::Starving => ("Starving", rltk::RED),
         };
-        y = y + 1;
+        ctx.print_color(71, 42, RGB::named(color), RGB::named(rltk::BLACK), text);
+    }
+
+    // between the experience bar and the statuses
+    let (weight, capacity) = player_load(ecs);
+    if weight > capacity {
+        ctx.print_color(
+            42,
+            42,
+            RGB::named(rltk::ORANGE),
+            RGB::named(rltk::BLACK),
+            "Burdened",
+        );
+    }
+
+    // one icon per status, left of the hunger state
+    let statuses = ecs.read_storage::<StatusEffects>();
+    for (_player, statuses) in (&players, &statuses).join() {
+        for (x, effect) in (50..).step_by(2).zip(statuses.effects.iter()) {
+            let (glyph, color) = effect.kind.icon();
+            ctx.set(
+                x,
+                42,
+                RGB::named(color),
+                RGB::named(rltk::BLACK),
+                rltk::to_cp437(glyph),
+            );
+        }
+    }
+
+    let experience = ecs.read_storage::<Experience>();
+    for (_player, xp) in (&players, &experience).join() {
+        let level = format!("Level {}", xp.level);
+        ctx.print_color(
+            2,
+            42,
+            RGB::named(rltk::MAGENTA),
+            RGB::named(rltk::BLACK),
+            &level,
+        );
+        ctx.draw_bar_horizontal(
+            12,
+            42,
+            30,
+            xp.xp,
+            xp.level * XP_PER_LEVEL,
+            RGB::named(rltk::GOLD),
+            RGB::named(rltk::BLACK),
+        );
+    }
+
+    let log = ecs.fetch::<GameLog>();
+    for (y, entry) in (44..49).zip(log.entries().rev()) {
+        print_log_entry(ctx, 2, y, entry);
     }
 
     let mouse_pos = ctx.mouse_pos();
@@ -90,6 +182,219 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     );
 }
 
+fn print_log_entry(ctx: &mut Rltk, x: i32, y: i32, entry: &LogEntry) {
+    let mut x = x;
+    for fragment in entry.fragments.iter() {
+        ctx.print_color(
+            x,
+            y,
+            fragment.color,
+            RGB::named(rltk::BLACK),
+            &fragment.text,
+        );
+        x += fragment.text.chars().count() as i32;
+    }
+}
+
+/// The whole log, newest at the bottom. `scroll` counts the lines hidden below,
+/// and `filter` keeps a single category.
+pub fn show_log(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    scroll: usize,
+    filter: Option<LogCategory>,
+) -> LogViewResult {
+    const LINES: usize = 45;
+
+    let log = gs.ecs.fetch::<GameLog>();
+    let entries: Vec<&LogEntry> = log
+        .entries()
+        .filter(|entry| filter.is_none_or(|category| entry.category == category))
+        .collect();
+    let max_scroll = entries.len().saturating_sub(LINES);
+    let scroll = usize::min(scroll, max_scroll);
+
+    ctx.draw_box(
+        0,
+        0,
+        79,
+        49,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    let title = format!(
+        "Message log: {}",
+        filter.map_or("All", |category| category.label())
+    );
+    ctx.print_color(
+        3,
+        0,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        title,
+    );
+    ctx.print_color(
+        3,
+        49,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Up/Down/PgUp/PgDn: scroll, Tab: filter, Escape: close",
+    );
+
+    let end = entries.len() - scroll;
+    let start = end.saturating_sub(LINES);
+    for (y, entry) in (2..).zip(entries[start..end].iter()) {
+        let turn = format!("{:>5}", entry.turn);
+        ctx.print_color(2, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), turn);
+        print_log_entry(ctx, 9, y, entry);
+    }
+
+    match ctx.key {
+        None => LogViewResult::NoResponse { scroll, filter },
+        Some(key) => match key {
+            rltk::VirtualKeyCode::Escape => LogViewResult::Close,
+            rltk::VirtualKeyCode::Up => LogViewResult::NoResponse {
+                scroll: usize::min(scroll + 1, max_scroll),
+                filter,
+            },
+            rltk::VirtualKeyCode::Down => LogViewResult::NoResponse {
+                scroll: scroll.saturating_sub(1),
+                filter,
+            },
+            rltk::VirtualKeyCode::PageUp => LogViewResult::NoResponse {
+                scroll: usize::min(scroll + LINES, max_scroll),
+                filter,
+            },
+            rltk::VirtualKeyCode::PageDown => LogViewResult::NoResponse {
+                scroll: scroll.saturating_sub(LINES),
+                filter,
+            },
+            rltk::VirtualKeyCode::Tab => {
+                let filter = match filter {
+                    None => Some(LogCategory::Combat),
+                    Some(LogCategory::Combat) => Some(LogCategory::Item),
+                    Some(LogCategory::Item) => Some(LogCategory::System),
+                    Some(LogCategory::System) => None,
+                };
+                LogViewResult::NoResponse { scroll: 0, filter }
+            }
+            _ => LogViewResult::NoResponse { scroll, filter },
+        },
+    }
+}
+
+/// The player's level, attributes, skills and what they wear in each slot,
+/// with the equipment bonuses added up.
+pub fn show_character(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let bonuses = gs.ecs.read_storage::<EquipmentBonus>();
+    let entities = gs.ecs.entities();
+
+    ctx.draw_box(
+        10,
+        6,
+        60,
+        24,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        13,
+        6,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Character",
+    );
+    ctx.print_color(
+        13,
+        30,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Escape to close",
+    );
+
+    if let Some(xp) = gs.ecs.read_storage::<Experience>().get(*player_entity) {
+        let level = format!(
+            "Level {}, {} / {} XP",
+            xp.level,
+            xp.xp,
+            xp.level * XP_PER_LEVEL
+        );
+        ctx.print(12, 8, level);
+    }
+    if let Some(attributes) = gs.ecs.read_storage::<Attributes>().get(*player_entity) {
+        let attributes = format!(
+            "Might {}  Fitness {}  Quickness {}  Intelligence {}",
+            attributes.might, attributes.fitness, attributes.quickness, attributes.intelligence
+        );
+        ctx.print(12, 9, attributes);
+    }
+    if let Some(skills) = gs.ecs.read_storage::<Skills>().get(*player_entity) {
+        let skills = format!(
+            "Melee {}  Ranged {}  Defense {}",
+            skills.melee, skills.ranged, skills.defense
+        );
+        ctx.print(12, 10, skills);
+    }
+
+    let mut y = 12;
+    for slot in EquipmentSlot::ALL {
+        let mut worn: Vec<&str> = (&entities, &equipped, &names)
+            .join()
+            .filter(|(_, e, _)| e.owner == *player_entity && e.slot == slot)
+            .map(|(_, _, name)| name.name.as_str())
+            .collect();
+        worn.resize(slot.capacity(), "-");
+        for name in worn {
+            ctx.print_color(
+                12,
+                y,
+                RGB::named(rltk::GREY),
+                RGB::named(rltk::BLACK),
+                slot.label(),
+            );
+            ctx.print_color(20, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), name);
+            y += 1;
+        }
+    }
+
+    let total = equipment_bonus(*player_entity, &equipped, &bonuses);
+    let total = format!(
+        "Power {:+}  Defense {:+}  Max HP {:+}  Vision {:+}  Speed {:+}",
+        total.power, total.defense, total.max_hp, total.vision, total.speed
+    );
+    ctx.print_color(
+        12,
+        y + 1,
+        RGB::named(rltk::GREEN),
+        RGB::named(rltk::BLACK),
+        total,
+    );
+
+    if let Some(statuses) = gs.ecs.read_storage::<StatusEffects>().get(*player_entity) {
+        let mut x = 12;
+        for effect in statuses.effects.iter() {
+            let (_, color) = effect.kind.icon();
+            let status = format!("{} ({})", effect.kind.label(), effect.turns);
+            ctx.print_color(
+                x,
+                y + 3,
+                RGB::named(color),
+                RGB::named(rltk::BLACK),
+                &status,
+            );
+            x += status.len() as i32 + 2;
+        }
+    }
+
+    match ctx.key {
+        Some(rltk::VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse,
+    }
+}
+
 pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
@@ -110,12 +415,13 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
     );
+    let (weight, capacity) = player_load(&gs.ecs);
     ctx.print_color(
         18,
         y - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Inventory",
+        format!("Inventory ({:.1} / {:.0} kg)", weight, capacity),
     );
     ctx.print_color(
         18,
@@ -153,7 +459,7 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -181,6 +487,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
@@ -189,7 +496,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
 
     let mut tooltip: Vec<String> = Vec::new();
 
-    for (name, position) in (&names, &positions).join() {
+    for (name, position, _hidden) in (&names, &positions, !&hidden).join() {
         let idx = map.get_index_at(position.x, position.y);
         if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
             tooltip.push(name.name.to_string());
@@ -335,7 +642,7 @@ pub fn show_drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, O
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -359,6 +666,38 @@ pub fn show_drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, O
     }
 }
 
+/// An item's name, with the count for a stack.
+fn item_label(ecs: &World, item: Entity, name: &Name) -> String {
+    let count = ecs
+        .read_storage::<Ammunition>()
+        .get(item)
+        .map(|ammo| ammo.count)
+        .or_else(|| ecs.read_storage::<Stackable>().get(item).map(|s| s.count));
+    match count {
+        Some(count) => format!("{} ({})", name.name, count),
+        None => name.name.clone(),
+    }
+}
+
+/// The weight the player carries, and what they can carry before slowing
+/// down, in kilograms.
+fn player_load(ecs: &World) -> (f32, f32) {
+    let player = *ecs.fetch::<Entity>();
+    let weight = carried_weight(
+        player,
+        &ecs.read_storage::<Item>(),
+        &ecs.read_storage::<InBackpack>(),
+        &ecs.read_storage::<Equipped>(),
+        &ecs.read_storage::<Stackable>(),
+        &ecs.read_storage::<Ammunition>(),
+    );
+    let capacity = ecs
+        .read_storage::<Attributes>()
+        .get(player)
+        .map_or(f32::MAX, |a| carry_capacity(a.might));
+    (weight, capacity)
+}
+
 pub fn ranged_target(
     gs: &mut State,
     ctx: &mut Rltk,
@@ -478,7 +817,7 @@ pub fn ranged_target(
 }
 
 pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
-    let save_exists = super::saveload_system::does_save_exist();
+    let save_exists = saveload_system::does_save_exist(&gs.ecs);
     let run_state = gs.ecs.fetch::<RunState>();
 
     ctx.print_color_centered(
@@ -508,17 +847,33 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
             );
         }
 
+        if selection == MainMenuSelection::NewPermadeathGame {
+            ctx.print_color_centered(
+                25,
+                RGB::named(rltk::MAGENTA),
+                RGB::named(rltk::BLACK),
+                "Begin new game (permadeath)",
+            );
+        } else {
+            ctx.print_color_centered(
+                25,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                "Begin new game (permadeath)",
+            );
+        }
+
         if save_exists {
             if selection == MainMenuSelection::LoadGame {
                 ctx.print_color_centered(
-                    25,
+                    26,
                     RGB::named(rltk::MAGENTA),
                     RGB::named(rltk::BLACK),
                     "Load game",
                 );
             } else {
                 ctx.print_color_centered(
-                    25,
+                    26,
                     RGB::named(rltk::WHITE),
                     RGB::named(rltk::BLACK),
                     "Load game",
@@ -528,13 +883,22 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
 
         if selection == MainMenuSelection::Quit {
             ctx.print_color_centered(
-                26,
+                27,
                 RGB::named(rltk::MAGENTA),
                 RGB::named(rltk::BLACK),
                 "Quit",
             );
         } else {
-            ctx.print_color_centered(26, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+            ctx.print_color_centered(27, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+        }
+
+        if let Some(message) = gs.ecs.try_fetch::<MenuMessage>() {
+            ctx.print_color_centered(
+                29,
+                RGB::named(rltk::RED),
+                RGB::named(rltk::BLACK),
+                &message.text,
+            );
         }
 
         match ctx.key {
@@ -553,12 +917,17 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                     let mut new_selection;
                     match selection {
                         MainMenuSelection::NewGame => new_selection = MainMenuSelection::Quit,
-                        MainMenuSelection::LoadGame => new_selection = MainMenuSelection::NewGame,
+                        MainMenuSelection::NewPermadeathGame => {
+                            new_selection = MainMenuSelection::NewGame
+                        }
+                        MainMenuSelection::LoadGame => {
+                            new_selection = MainMenuSelection::NewPermadeathGame
+                        }
                         MainMenuSelection::Quit => new_selection = MainMenuSelection::LoadGame,
                     }
 
                     if new_selection == MainMenuSelection::LoadGame && !save_exists {
-                        new_selection = MainMenuSelection::NewGame;
+                        new_selection = MainMenuSelection::NewPermadeathGame;
                     }
                     return MainMenuResult::NoSelection {
                         selected: new_selection,
@@ -567,7 +936,12 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                 rltk::VirtualKeyCode::Down => {
                     let mut new_selection;
                     match selection {
-                        MainMenuSelection::NewGame => new_selection = MainMenuSelection::LoadGame,
+                        MainMenuSelection::NewGame => {
+                            new_selection = MainMenuSelection::NewPermadeathGame
+                        }
+                        MainMenuSelection::NewPermadeathGame => {
+                            new_selection = MainMenuSelection::LoadGame
+                        }
                         MainMenuSelection::LoadGame => new_selection = MainMenuSelection::Quit,
                         MainMenuSelection::Quit => new_selection = MainMenuSelection::NewGame,
                     }
@@ -597,6 +971,148 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     }
 }
 
+pub fn save_slot_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    mode: SlotMenuMode,
+    selection: usize,
+) -> SlotMenuResult {
+    let title = match mode {
+        SlotMenuMode::Save => "Save game",
+        SlotMenuMode::Load => "Load game",
+    };
+    let y = 25 - SAVE_SLOTS as i32;
+    ctx.draw_box(
+        2,
+        y - 2,
+        75,
+        SAVE_SLOTS * 2 + 4,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        5,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        title,
+    );
+    ctx.print_color(
+        5,
+        y + SAVE_SLOTS as i32 * 2 + 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Up/Down: choose, Enter: select, Delete: erase, Escape: cancel",
+    );
+
+    let slots = gs.ecs.fetch::<SaveSlots>();
+    let occupied: Vec<bool> = slots.headers.iter().map(Option::is_some).collect();
+    for (slot, header) in slots.headers.iter().enumerate() {
+        let mut permadeath = false;
+        let description = match header {
+            None => "empty".to_string(),
+            Some(Err(e)) => e.to_string(),
+            Some(Ok(header)) => match &header.metadata {
+                None => format!("version {}", header.game_version),
+                Some(metadata) => {
+                    permadeath = metadata.permadeath;
+                    format!(
+                        "Depth {}, HP {}/{}, turn {}, {}, seed {}",
+                        metadata.depth,
+                        metadata.hp,
+                        metadata.max_hp,
+                        metadata.turns,
+                        format_timestamp(metadata.timestamp),
+                        metadata.seed
+                    )
+                }
+            },
+        };
+
+        let fg = if slot == selection {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color(
+            5,
+            y + slot as i32 * 2,
+            fg,
+            RGB::named(rltk::BLACK),
+            format!("Slot {}: {}", slot + 1, description),
+        );
+        if permadeath {
+            ctx.print_color(
+                13,
+                y + slot as i32 * 2 + 1,
+                RGB::named(rltk::RED),
+                RGB::named(rltk::BLACK),
+                "Permadeath: loading this save deletes it",
+            );
+        }
+    }
+
+    if let Some(message) = gs.ecs.try_fetch::<MenuMessage>() {
+        ctx.print_color_centered(
+            y + SAVE_SLOTS as i32 * 2 + 4,
+            RGB::named(rltk::RED),
+            RGB::named(rltk::BLACK),
+            &message.text,
+        );
+    }
+
+    match ctx.key {
+        None => SlotMenuResult::NoSelection {
+            selected: selection,
+        },
+        Some(key) => match key {
+            rltk::VirtualKeyCode::Escape => SlotMenuResult::Cancel,
+            rltk::VirtualKeyCode::Up => SlotMenuResult::NoSelection {
+                selected: (selection + SAVE_SLOTS - 1) % SAVE_SLOTS,
+            },
+            rltk::VirtualKeyCode::Down => SlotMenuResult::NoSelection {
+                selected: (selection + 1) % SAVE_SLOTS,
+            },
+            rltk::VirtualKeyCode::Delete if occupied[selection] => {
+                SlotMenuResult::Delete { slot: selection }
+            }
+            rltk::VirtualKeyCode::Return if mode == SlotMenuMode::Save || occupied[selection] => {
+                SlotMenuResult::Selected { slot: selection }
+            }
+            _ => SlotMenuResult::NoSelection {
+                selected: selection,
+            },
+        },
+    }
+}
+
+/// Formats seconds since the unix epoch as a UTC date and time.
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds_of_day = timestamp % 86400;
+
+    // civil date from days since 1970-01-01, proleptic gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60
+    )
+}
+
 pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
@@ -660,7 +1176,7 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -684,7 +1200,7 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
+pub fn game_over(ctx: &mut Rltk, seed: u64) -> GameOverResult {
     ctx.print_color_centered(
         15,
         RGB::named(rltk::YELLOW),
@@ -703,9 +1219,15 @@ pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
         RGB::named(rltk::BLACK),
         "That day, sadly, is not in this chapter..",
     );
+    ctx.print_color_centered(
+        19,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+        format!("Seed: {} (replay with --seed {})", seed, seed),
+    );
 
     ctx.print_color_centered(
-        20,
+        21,
         RGB::named(rltk::MAGENTA),
         RGB::named(rltk::BLACK),
         "Press any key to return to the menu.",
@@ -0,0 +1,289 @@
+//! Fighting: melee, shooting, area spells and the XP they earn.
+
+use super::{
+    fixtures::{
+        aim_at, arm_with_shortbow, clear_monsters, fire_at, free_neighbour, player, player_stats,
+        position_of, spawn, stand_before, text, use_save_directory, SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{
+        Ammunition, Brain, CombatStats, Experience, Monster, Position, Skills, StatusEffects,
+        StatusKind,
+    },
+    damage_system::{XP_PER_LEVEL, XP_PER_VICTIM_LEVEL},
+    gamelog::GameLog,
+    map::TileType,
+    saveload_system, RunState,
+};
+use rltk::{Point, VirtualKeyCode};
+use specs::prelude::*;
+use std::fs;
+
+#[test]
+fn player_kills_a_goblin() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    let goblin = spawn(&mut game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+
+    fight(&mut game, goblin, dx, dy);
+
+    assert!(!game.world().entities().is_alive(goblin));
+    assert_eq!(position_of(&game, player(&game)), pos);
+    let log = game.world().fetch::<GameLog>();
+    assert!(log.entries().any(|entry| text(entry) == "Goblin is dead"));
+}
+
+/// Attacks the target at `dx, dy` until it dies, failing past a hundred tries.
+/// The target stands its ground.
+fn fight(game: &mut HeadlessGame, target: Entity, dx: i32, dy: i32) {
+    game.world()
+        .write_storage::<Brain>()
+        .get_mut(target)
+        .unwrap()
+        .flee_below = 0;
+    for _ in 0..100 {
+        if !game.world().entities().is_alive(target) {
+            return;
+        }
+        game.act(Action::Move { dx, dy });
+    }
+    panic!("the fight went on forever");
+}
+
+#[test]
+fn natural_rolls_decide_against_a_perfect_defense() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    let goblin = spawn(&mut game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+    game.world()
+        .write_storage::<Skills>()
+        .get_mut(goblin)
+        .unwrap()
+        .defense = 100;
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 1000;
+
+    for _ in 0..60 {
+        game.act(Action::Move { dx, dy });
+    }
+
+    // only a natural 20 gets through, and it is a critical hit
+    let log = game.world().fetch::<GameLog>();
+    let attacks: Vec<String> = log
+        .entries()
+        .map(text)
+        .filter(|text| text.starts_with("Player"))
+        .collect();
+    assert_eq!(attacks.len(), 60);
+    assert!(attacks.iter().any(|text| text == "Player misses Goblin"));
+    assert!(attacks
+        .iter()
+        .any(|text| text == "Player fumbles, missing Goblin"));
+    assert!(attacks
+        .iter()
+        .any(|text| text.starts_with("Player lands a critical hit on Goblin, for")));
+    assert!(!attacks.iter().any(|text| text.starts_with("Player hurts")));
+}
+
+#[test]
+fn killing_earns_xp_and_levels_up() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    let goblin = spawn(&mut game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+    let player = player(&game);
+    let before = game
+        .world()
+        .read_storage::<CombatStats>()
+        .get(player)
+        .unwrap()
+        .clone();
+    game.world()
+        .write_storage::<Experience>()
+        .get_mut(player)
+        .unwrap()
+        .xp = XP_PER_LEVEL - XP_PER_VICTIM_LEVEL;
+
+    fight(&mut game, goblin, dx, dy);
+
+    let experience = game.world().read_storage::<Experience>();
+    let xp = experience.get(player).unwrap();
+    assert_eq!((xp.level, xp.xp), (2, 0));
+    let stats = game.world().read_storage::<CombatStats>();
+    let stats = stats.get(player).unwrap();
+    // 10, plus 1 for the player's fitness
+    assert_eq!(stats.max_hp, before.max_hp + 11);
+    assert_eq!(stats.hp, stats.max_hp);
+    let skills = game.world().read_storage::<Skills>();
+    let skills = skills.get(player).unwrap();
+    assert_eq!((skills.melee, skills.defense), (2, 2));
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "Congratulations, you are now level 2!"));
+}
+
+#[test]
+fn poison_kills_earn_the_poisoner_xp_even_across_a_save() {
+    let mut game = HeadlessGame::new(SEED);
+    let save_dir = use_save_directory(&mut game, "poison_kills");
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let goblin = spawn(&mut game, "Goblin", Point::new(start.x + 3, start.y));
+    let level = game
+        .world()
+        .read_storage::<Experience>()
+        .get(goblin)
+        .unwrap()
+        .level;
+    spawn(&mut game, "Flask of Poison", start);
+    game.act(Action::PickUp);
+    game.act(Action::UseItem(0));
+    let target = position_of(&game, goblin);
+    aim_at(&mut game, target);
+    // three turns of poison left to live
+    game.world_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 3;
+    saveload_system::save_game(game.world_mut(), 0).unwrap();
+    saveload_system::load_game(game.world_mut(), 0).unwrap();
+
+    for _ in 0..3 {
+        game.act(Action::Wait);
+    }
+    assert!(game
+        .world()
+        .read_storage::<Monster>()
+        .join()
+        .next()
+        .is_none());
+    let player = player(&game);
+    let experience = game.world().read_storage::<Experience>();
+    assert_eq!(
+        experience.get(player).unwrap().xp,
+        level * XP_PER_VICTIM_LEVEL
+    );
+    fs::remove_dir_all(save_dir).unwrap();
+}
+
+#[test]
+fn a_fireball_hurts_and_burns_everything_in_the_blast() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let goblins = [
+        spawn(&mut game, "Goblin", Point::new(start.x + 3, start.y)),
+        spawn(&mut game, "Goblin", Point::new(start.x + 4, start.y)),
+    ];
+    for goblin in goblins {
+        let mut stats = game.world_mut().write_storage::<CombatStats>();
+        let stats = stats.get_mut(goblin).unwrap();
+        stats.max_hp = 100;
+        stats.hp = 100;
+    }
+    let scroll = spawn(&mut game, "Fireball Scroll", start);
+    game.act(Action::PickUp);
+
+    game.act(Action::UseItem(0));
+    aim_at(&mut game, Point::new(start.x + 3, start.y));
+
+    assert!(!game.world().entities().is_alive(scroll));
+    let stats = game.world().read_storage::<CombatStats>();
+    let statuses = game.world().read_storage::<StatusEffects>();
+    for goblin in goblins {
+        assert_eq!(stats.get(goblin).unwrap().hp, 80);
+        assert!(statuses.get(goblin).unwrap().has(StatusKind::Burning));
+    }
+    assert_eq!(player_stats(&game).hp, player_stats(&game).max_hp);
+}
+
+fn ammo_count(game: &HeadlessGame, ammo: Entity) -> i32 {
+    game.world()
+        .read_storage::<Ammunition>()
+        .get(ammo)
+        .unwrap()
+        .count
+}
+
+/// Ammunition lying on the floor at `pos`.
+fn ammo_on_floor(game: &HeadlessGame, pos: Point) -> Vec<Entity> {
+    let entities = game.world().entities();
+    let ammunition = game.world().read_storage::<Ammunition>();
+    let positions = game.world().read_storage::<Position>();
+    (&entities, &ammunition, &positions)
+        .join()
+        .filter(|(_, _, p)| p.x == pos.x && p.y == pos.y)
+        .map(|(e, _, _)| e)
+        .collect()
+}
+
+#[test]
+fn arrows_stop_at_the_first_creature() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 5]);
+    let arrows = arm_with_shortbow(&mut game);
+    spawn(&mut game, "Goblin", Point::new(start.x + 2, start.y));
+
+    fire_at(&mut game, Point::new(start.x + 4, start.y));
+
+    assert_eq!(ammo_count(&game, arrows), 11);
+    assert_eq!(
+        ammo_on_floor(&game, Point::new(start.x + 2, start.y)).len(),
+        1
+    );
+    let log = game.world().fetch::<GameLog>();
+    assert!(log.entries().any(|entry| {
+        let text = text(entry);
+        text.starts_with("Player") && text.contains("Goblin")
+    }));
+}
+
+#[test]
+fn arrows_stop_at_walls_and_can_be_picked_up_again() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let row = [
+        TileType::Floor,
+        TileType::Floor,
+        TileType::Floor,
+        TileType::Wall,
+    ];
+    let start = stand_before(&mut game, &row);
+    let wall = Point::new(start.x + 3, start.y);
+    let arrows = arm_with_shortbow(&mut game);
+
+    fire_at(&mut game, wall);
+    let landing = Point::new(start.x + 2, start.y);
+    let fired = ammo_on_floor(&game, landing);
+    assert_eq!(fired.len(), 1);
+    assert_eq!(ammo_count(&game, fired[0]), 1);
+
+    // walking over it and picking it up puts it back in the quiver
+    game.run_script(&[
+        Action::Move { dx: 1, dy: 0 },
+        Action::Move { dx: 1, dy: 0 },
+        Action::PickUp,
+    ]);
+    assert!(!game.world().entities().is_alive(fired[0]));
+    assert_eq!(ammo_count(&game, arrows), 12);
+}
+
+#[test]
+fn firing_without_a_ranged_weapon_is_refused() {
+    let mut game = HeadlessGame::new(SEED);
+    game.press(VirtualKeyCode::F);
+    assert!(game.run_state() == RunState::AwaitingInput);
+}
@@ -0,0 +1,310 @@
+//! Finding the way around a level: exploring, travelling, traps and doors.
+
+use super::{
+    fixtures::{
+        clear_monsters, far_away, player, player_stats, position_of, spawn, stand_before, teleport,
+        text, tile_under_player, SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{Door, EntryTrigger, Hidden, Position, Viewshed},
+    gamelog::GameLog,
+    map::{Map, TileType},
+    map_indexing_system::MapIndexingSystem,
+    RunState,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+fn revealed_tiles(game: &HeadlessGame) -> usize {
+    let map = game.world().fetch::<Map>();
+    map.revealed_tiles
+        .iter()
+        .filter(|revealed| **revealed)
+        .count()
+}
+
+fn last_log_entry(game: &HeadlessGame) -> String {
+    text(game.world().fetch::<GameLog>().entries().last().unwrap())
+}
+
+#[test]
+fn exploring_reveals_the_level() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let before = revealed_tiles(&game);
+
+    game.act(Action::Explore);
+
+    assert!(revealed_tiles(&game) > before * 2);
+    assert!(game.run_state() == RunState::AwaitingInput);
+    assert_eq!(last_log_entry(&game), "There is nothing left to explore.");
+}
+
+#[test]
+fn exploring_stops_when_a_monster_shows_up() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let hideout = far_away(&game, position_of(&game, player(&game)));
+    let goblin = spawn(&mut game, "Goblin", hideout);
+
+    game.act(Action::Explore);
+
+    assert!(game.run_state() == RunState::AwaitingInput);
+    let player = player(&game);
+    let viewsheds = game.world().read_storage::<Viewshed>();
+    let in_sight = &viewsheds.get(player).unwrap().visible_tiles;
+    assert!(in_sight.contains(&position_of(&game, goblin)));
+    // it may have struck first, which stops the player too
+    let log = game.world().fetch::<GameLog>();
+    assert!(log.entries().any(|entry| {
+        let text = text(entry);
+        text == "You stop, the Goblin is in sight."
+            || text.starts_with("Goblin") && text.contains("Player")
+    }));
+}
+
+/// Lets the player know the whole level, as if they had explored it.
+fn reveal_map(game: &mut HeadlessGame) {
+    let mut map = game.world().fetch_mut::<Map>();
+    map.revealed_tiles
+        .iter_mut()
+        .for_each(|revealed| *revealed = true);
+}
+
+fn clear_traps(game: &mut HeadlessGame) {
+    let traps: Vec<Entity> = {
+        let entities = game.world().entities();
+        let triggers = game.world().read_storage::<EntryTrigger>();
+        (&entities, &triggers).join().map(|(e, _)| e).collect()
+    };
+    for trap in traps {
+        game.world_mut().delete_entity(trap).unwrap();
+    }
+    game.world_mut().maintain();
+    MapIndexingSystem {}.run_now(game.world());
+}
+
+#[test]
+fn travelling_to_the_stairs() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    clear_traps(&mut game);
+    reveal_map(&mut game);
+
+    game.act(Action::TravelToStairs);
+
+    assert!(tile_under_player(&game) == TileType::DownStairs);
+    assert!(game.run_state() == RunState::AwaitingInput);
+}
+
+#[test]
+fn the_stairs_must_be_found_before_travelling_there() {
+    let mut game = HeadlessGame::new(SEED);
+    {
+        let mut map = game.world().fetch_mut::<Map>();
+        map.revealed_tiles
+            .iter_mut()
+            .for_each(|revealed| *revealed = false);
+    }
+    let start = position_of(&game, player(&game));
+
+    game.act(Action::TravelToStairs);
+
+    assert_eq!(position_of(&game, player(&game)), start);
+    assert_eq!(
+        last_log_entry(&game),
+        "You haven't found the stairs down yet."
+    );
+}
+
+#[test]
+fn clicking_a_known_tile_travels_there() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    clear_traps(&mut game);
+    reveal_map(&mut game);
+    let destination = far_away(&game, position_of(&game, player(&game)));
+
+    game.click(destination);
+
+    assert_eq!(position_of(&game, player(&game)), destination);
+}
+
+#[test]
+fn travel_stops_when_the_player_gets_hurt() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    clear_traps(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    spawn(&mut game, "Bear Trap", Point::new(start.x + 2, start.y));
+
+    game.click(Point::new(start.x + 5, start.y));
+
+    assert_eq!(
+        position_of(&game, player(&game)),
+        Point::new(start.x + 2, start.y)
+    );
+    assert!(game.run_state() == RunState::AwaitingInput);
+}
+
+fn is_hidden(game: &HeadlessGame, entity: Entity) -> bool {
+    game.world().read_storage::<Hidden>().contains(entity)
+}
+
+#[test]
+fn hidden_traps_go_off_when_stepped_on() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 3]);
+    let trap = spawn(&mut game, "Bear Trap", Point::new(start.x + 1, start.y));
+    assert!(is_hidden(&game, trap));
+    let hp = player_stats(&game).hp;
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+
+    assert_eq!(player_stats(&game).hp, hp - 6);
+    // a bear trap only closes once
+    assert!(!game.world().entities().is_alive(trap));
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "Player sets off the Bear Trap!"));
+}
+
+#[test]
+fn searching_reveals_nearby_traps() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 3]);
+    let trap = spawn(
+        &mut game,
+        "Poison Dart Trap",
+        Point::new(start.x + 2, start.y),
+    );
+
+    for _ in 0..20 {
+        if !is_hidden(&game, trap) {
+            break;
+        }
+        game.act(Action::Search);
+    }
+
+    assert!(!is_hidden(&game, trap));
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "You spot a Poison Dart Trap."));
+    let map = game.world().fetch::<Map>();
+    assert!(map
+        .known_traps
+        .contains(&map.get_index_at(start.x + 2, start.y)));
+}
+
+#[test]
+fn paths_go_around_known_traps() {
+    // a 5x3 room, with a trap in the middle of the straight way across
+    let mut map = Map::new(1);
+    for y in 1..4 {
+        for x in 1..6 {
+            let idx = map.get_index_at(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+    map.populate_blocked();
+    let trap = map.get_index_at(3, 2);
+    let (from, to) = (map.get_index_at(1, 2), map.get_index_at(5, 2));
+    assert!(rltk::a_star_search(from, to, &map).steps.contains(&trap));
+
+    map.known_traps.insert(trap);
+    let path = rltk::a_star_search(from, to, &map);
+    assert!(path.success);
+    assert!(!path.steps.contains(&trap));
+}
+
+/// Puts the player in front of the first door of the level, returning the
+/// door and the move that bumps into it.
+fn stand_before_door(game: &mut HeadlessGame) -> (Entity, i32, i32) {
+    let (door, door_pos, (dx, dy)) = {
+        let entities = game.world().entities();
+        let doors = game.world().read_storage::<Door>();
+        let positions = game.world().read_storage::<Position>();
+        let map = game.world().fetch::<Map>();
+        let (door, _, pos) = (&entities, &doors, &positions)
+            .join()
+            .next()
+            .expect("No door on the level");
+        let side = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .find(|(dx, dy)| {
+                let idx = map.get_index_at(pos.x + dx, pos.y + dy);
+                map.tiles[idx] == TileType::Floor && !map.blocked[idx]
+            })
+            .unwrap();
+        (door, Point::new(pos.x, pos.y), side)
+    };
+    teleport(game, Point::new(door_pos.x + dx, door_pos.y + dy));
+    game.act(Action::Wait);
+    (door, -dx, -dy)
+}
+
+fn door_is_open(game: &HeadlessGame, door: Entity) -> bool {
+    game.world().read_storage::<Door>().get(door).unwrap().open
+}
+
+#[test]
+fn closed_doors_block_sight_and_open_when_bumped() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (door, dx, dy) = stand_before_door(&mut game);
+    game.world()
+        .write_storage::<Door>()
+        .get_mut(door)
+        .unwrap()
+        .locked = false;
+    let start = position_of(&game, player(&game));
+    let beyond = Point::new(start.x + 2 * dx, start.y + 2 * dy);
+    let sees_beyond = |game: &HeadlessGame| {
+        let map = game.world().fetch::<Map>();
+        map.visible_tiles[map.get_index_at(beyond.x, beyond.y)]
+    };
+    assert!(!sees_beyond(&game));
+
+    game.act(Action::Move { dx, dy });
+    assert!(door_is_open(&game, door));
+    assert_eq!(position_of(&game, player(&game)), start);
+    assert!(sees_beyond(&game));
+
+    game.act(Action::Move { dx, dy });
+    assert_eq!(
+        position_of(&game, player(&game)),
+        Point::new(start.x + dx, start.y + dy)
+    );
+}
+
+#[test]
+fn locked_doors_need_a_key() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (door, dx, dy) = stand_before_door(&mut game);
+    game.world()
+        .write_storage::<Door>()
+        .get_mut(door)
+        .unwrap()
+        .locked = true;
+
+    game.act(Action::Move { dx, dy });
+    assert!(!door_is_open(&game, door));
+
+    let pos = position_of(&game, player(&game));
+    let key = spawn(&mut game, "Iron Key", pos);
+    game.act(Action::PickUp);
+    game.act(Action::Move { dx, dy });
+    assert!(door_is_open(&game, door));
+    assert!(!game.world().entities().is_alive(key));
+    let log = game.world().fetch::<GameLog>();
+    let texts: Vec<String> = log.entries().map(text).collect();
+    assert!(texts.iter().any(|text| text == "The door is locked."));
+    assert!(texts.iter().any(|text| text == "You unlock the door."));
+}
@@ -0,0 +1,229 @@
+//! Helpers shared by the scenarios, and by the tests of the systems that need
+//! a whole game to run.
+
+use super::{Action, HeadlessGame};
+use crate::{
+    components::{
+        CombatStats, Energy, EquipmentSlot, Equipped, InBackpack, Monster, Position, Viewshed,
+    },
+    gamelog::LogEntry,
+    initiative_system::{NORMAL_SPEED, TURN_COST},
+    map::{Map, TileType},
+    map_indexing_system::MapIndexingSystem,
+    raws::{spawn_named_entity, SpawnType, RAWS},
+    saveload_system::{self, SaveDirectory},
+    Aiming, RunState,
+};
+use rltk::{Point, VirtualKeyCode};
+use specs::prelude::*;
+use std::{env, fs, path::PathBuf, process};
+
+pub const SEED: u64 = 1234;
+
+/// A log entry without its colors.
+pub fn text(entry: &LogEntry) -> String {
+    entry
+        .fragments
+        .iter()
+        .map(|fragment| fragment.text.as_str())
+        .collect()
+}
+
+pub fn player(game: &HeadlessGame) -> Entity {
+    *game.world().fetch::<Entity>()
+}
+
+pub fn player_stats(game: &HeadlessGame) -> CombatStats {
+    let player = player(game);
+    game.world()
+        .read_storage::<CombatStats>()
+        .get(player)
+        .unwrap()
+        .clone()
+}
+
+/// Removes every monster so that nothing interferes with the scenario.
+pub fn clear_monsters(game: &mut HeadlessGame) {
+    let monsters: Vec<Entity> = {
+        let entities = game.world().entities();
+        let monsters = game.world().read_storage::<Monster>();
+        (&entities, &monsters).join().map(|(e, _)| e).collect()
+    };
+    for monster in monsters {
+        game.world_mut().delete_entity(monster).unwrap();
+    }
+    game.world_mut().maintain();
+}
+
+pub fn spawn(game: &mut HeadlessGame, name: &str, at: Point) -> Entity {
+    let raws = RAWS.lock().unwrap();
+    let entity = spawn_named_entity(
+        &raws,
+        game.world_mut().create_entity(),
+        name,
+        SpawnType::AtPosition { x: at.x, y: at.y },
+    )
+    .unwrap();
+    game.world_mut().maintain();
+    // index it right away, like the spawns of a new level
+    MapIndexingSystem {}.run_now(game.world());
+    entity
+}
+
+/// An empty floor tile next to the player and the move that reaches it.
+pub fn free_neighbour(game: &HeadlessGame) -> (i32, i32) {
+    let pos = position_of(game, player(game));
+    let map = game.world().fetch::<Map>();
+    for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+        let idx = map.get_index_at(pos.x + dx, pos.y + dy);
+        if map.tiles[idx] == TileType::Floor && !map.blocked[idx] {
+            return (*dx, *dy);
+        }
+    }
+    panic!("Player is walled in");
+}
+
+/// Makes `creature` of normal speed, acting right after each of the player's
+/// turns.
+pub fn in_step(game: &mut HeadlessGame, creature: Entity) {
+    game.world()
+        .write_storage::<Energy>()
+        .insert(
+            creature,
+            Energy {
+                speed: NORMAL_SPEED,
+                current: TURN_COST - 1,
+            },
+        )
+        .unwrap();
+}
+
+pub fn position_of(game: &HeadlessGame, entity: Entity) -> Point {
+    let positions = game.world().read_storage::<Position>();
+    let pos = positions.get(entity).unwrap();
+    Point::new(pos.x, pos.y)
+}
+
+/// A tile that can be walked to from `from`, but well out of sight.
+pub fn far_away(game: &HeadlessGame, from: Point) -> Point {
+    let map = game.world().fetch::<Map>();
+    let start = map.get_index_at(from.x, from.y);
+    let dijkstra_map = rltk::DijkstraMap::new(
+        map.width as usize,
+        map.height as usize,
+        &[start],
+        &*map,
+        (map.width * map.height) as f32,
+    );
+    (0..map.tiles.len())
+        .filter(|idx| dijkstra_map.map[*idx] > 12.0 && dijkstra_map.map[*idx] < 20.0)
+        .map(|idx| map.get_xy_from_idx(idx))
+        .map(|(x, y)| Point::new(x, y))
+        .find(|p| rltk::DistanceAlg::Pythagoras.distance2d(*p, from) > 12.0)
+        .expect("No tile far enough")
+}
+
+/// Puts the player at the start of the first row of tiles matching
+/// `row`, with a fresh field of view.
+pub fn stand_before(game: &mut HeadlessGame, row: &[TileType]) -> Point {
+    let start = {
+        let map = game.world().fetch::<Map>();
+        (0..map.tiles.len())
+            .map(|idx| map.get_xy_from_idx(idx))
+            .find(|(x, y)| {
+                x + (row.len() as i32) < map.width
+                    && row
+                        .iter()
+                        .enumerate()
+                        .all(|(i, tile)| map.tiles[map.get_index_at(x + i as i32, *y)] == *tile)
+            })
+            .map(|(x, y)| Point::new(x, y))
+            .expect("No such row of tiles")
+    };
+    teleport(game, start);
+    game.act(Action::Wait);
+    start
+}
+
+/// Gives the player a bow and arrows, returning the arrows.
+pub fn arm_with_shortbow(game: &mut HeadlessGame) -> Entity {
+    let player = player(game);
+    let bow = spawn(game, "Shortbow", Point::new(0, 0));
+    let arrows = spawn(game, "Arrows", Point::new(0, 0));
+    let world = game.world_mut();
+    world.write_storage::<Position>().remove(bow);
+    world.write_storage::<Position>().remove(arrows);
+    world
+        .write_storage::<Equipped>()
+        .insert(
+            bow,
+            Equipped {
+                owner: player,
+                slot: EquipmentSlot::Ranged,
+            },
+        )
+        .unwrap();
+    world
+        .write_storage::<InBackpack>()
+        .insert(arrows, InBackpack { owner: player })
+        .unwrap();
+    arrows
+}
+
+pub fn fire_at(game: &mut HeadlessGame, target: Point) {
+    game.press(VirtualKeyCode::F);
+    aim_at(game, target);
+}
+
+/// Picks `target` on the targeting screen.
+pub fn aim_at(game: &mut HeadlessGame, target: Point) {
+    assert!(matches!(game.run_state(), RunState::ShowTargeting { .. }));
+    *game.world_mut().write_resource::<Aiming>() = Aiming {
+        x: target.x,
+        y: target.y,
+    };
+    game.press(VirtualKeyCode::Space);
+}
+
+pub fn stand_on(game: &mut HeadlessGame, tile_type: TileType) -> Point {
+    let (x, y) = {
+        let map = game.world().fetch::<Map>();
+        let idx = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == tile_type)
+            .unwrap();
+        map.get_xy_from_idx(idx)
+    };
+    teleport(game, Point::new(x, y));
+    Point::new(x, y)
+}
+
+pub fn teleport(game: &mut HeadlessGame, to: Point) {
+    {
+        let player = player(game);
+        let mut positions = game.world().write_storage::<Position>();
+        let pos = positions.get_mut(player).unwrap();
+        pos.x = to.x;
+        pos.y = to.y;
+        let mut viewsheds = game.world().write_storage::<Viewshed>();
+        viewsheds.get_mut(player).unwrap().dirty = true;
+    }
+    *game.world_mut().write_resource::<Point>() = to;
+}
+
+pub fn tile_under_player(game: &HeadlessGame) -> TileType {
+    let pos = position_of(game, player(game));
+    let map = game.world().fetch::<Map>();
+    map.tiles[map.get_index_at(pos.x, pos.y)]
+}
+
+/// Points the game at an empty save directory of its own.
+pub fn use_save_directory(game: &mut HeadlessGame, name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("rltk_game_{}_{}", process::id(), name));
+    let _ = fs::remove_dir_all(&path);
+    game.world_mut()
+        .insert(SaveDirectory { path: path.clone() });
+    saveload_system::refresh_save_slots(game.world());
+    path
+}
@@ -0,0 +1,72 @@
+//! The log and the screens the player opens.
+
+use super::{
+    fixtures::{clear_monsters, free_neighbour, player, position_of, spawn, text, SEED},
+    Action, HeadlessGame,
+};
+use crate::{
+    components::CombatStats,
+    gamelog::{GameLog, LogCategory, LogEntry},
+    RunState, TurnCounter,
+};
+use rltk::{Point, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+#[test]
+fn combat_is_logged_in_color_with_its_turn() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    let goblin = spawn(&mut game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 1000;
+    game.act(Action::Wait);
+
+    // until the first ordinary hit, misses and criticals aside
+    let is_hit = |entry: &LogEntry| text(entry).starts_with("Player hurts Goblin, for");
+    let mut turn = 0;
+    while !game.world().fetch::<GameLog>().entries().any(is_hit) {
+        turn = game.world().fetch::<TurnCounter>().turns;
+        game.act(Action::Move { dx, dy });
+    }
+
+    let log = game.world().fetch::<GameLog>();
+    let hit = log.entries().find(|entry| is_hit(entry)).unwrap();
+    assert!(hit.category == LogCategory::Combat);
+    assert_eq!(hit.turn, turn);
+    let damage = &hit.fragments[hit.fragments.len() - 2];
+    assert!(damage.text.trim().parse::<i32>().unwrap() > 0);
+    assert!(damage.color == RGB::named(rltk::RED));
+    let name = hit.fragments.iter().find(|f| f.text == "Player").unwrap();
+    assert!(name.color == RGB::named(rltk::YELLOW));
+}
+
+#[test]
+fn the_log_viewer_opens_and_closes() {
+    let mut game = HeadlessGame::new(SEED);
+    game.press(VirtualKeyCode::M);
+    assert!(matches!(game.run_state(), RunState::ShowLog { .. }));
+    game.press(VirtualKeyCode::Tab);
+    assert!(
+        game.run_state()
+            == RunState::ShowLog {
+                scroll: 0,
+                filter: Some(LogCategory::Combat)
+            }
+    );
+    game.press(VirtualKeyCode::Escape);
+    assert!(game.run_state() == RunState::AwaitingInput);
+}
+
+#[test]
+fn the_character_sheet_opens_and_closes() {
+    let mut game = HeadlessGame::new(SEED);
+    game.press(VirtualKeyCode::C);
+    assert!(game.run_state() == RunState::ShowCharacter);
+    game.press(VirtualKeyCode::Escape);
+    assert!(game.run_state() == RunState::AwaitingInput);
+}
@@ -0,0 +1,158 @@
+//! Picking up, using, equipping and dropping items.
+
+use super::{
+    fixtures::{clear_monsters, player, player_stats, position_of, spawn, SEED},
+    Action, HeadlessGame,
+};
+use crate::components::{CombatStats, Equipped, InBackpack, Item, Name, Position, Stackable};
+use rltk::Point;
+use specs::prelude::*;
+
+#[test]
+fn pick_up_and_drink_a_potion() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let pos = position_of(&game, player(&game));
+    let potion = spawn(&mut game, "Health Potion", pos);
+    {
+        let player = player(&game);
+        let mut stats = game.world_mut().write_storage::<CombatStats>();
+        stats.get_mut(player).unwrap().hp = 100;
+    }
+
+    game.act(Action::PickUp);
+    assert!(game
+        .world()
+        .read_storage::<InBackpack>()
+        .get(potion)
+        .is_some());
+
+    game.act(Action::UseItem(0));
+    assert!(!game.world().entities().is_alive(potion));
+    assert_eq!(player_stats(&game).hp, 108);
+}
+
+#[test]
+fn equip_remove_and_drop_a_dagger() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let pos = position_of(&game, player(&game));
+    let dagger = spawn(&mut game, "Dagger", pos);
+
+    game.run_script(&[Action::PickUp, Action::UseItem(0)]);
+    assert!(game
+        .world()
+        .read_storage::<Equipped>()
+        .get(dagger)
+        .is_some());
+
+    game.act(Action::RemoveItem(0));
+    assert!(game
+        .world()
+        .read_storage::<Equipped>()
+        .get(dagger)
+        .is_none());
+    assert!(game
+        .world()
+        .read_storage::<InBackpack>()
+        .get(dagger)
+        .is_some());
+
+    game.act(Action::DropItem(0));
+    assert!(game
+        .world()
+        .read_storage::<InBackpack>()
+        .get(dagger)
+        .is_none());
+    let positions = game.world().read_storage::<Position>();
+    let dropped = positions.get(dagger).unwrap();
+    assert_eq!((dropped.x, dropped.y), (pos.x, pos.y));
+}
+
+#[test]
+fn two_rings_can_be_worn_and_bonuses_follow_the_equipment() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let pos = position_of(&game, player(&game));
+    let rings: Vec<Entity> = (0..3)
+        .map(|_| spawn(&mut game, "Ring of Vitality", pos))
+        .collect();
+    let max_hp = player_stats(&game).max_hp;
+
+    game.run_script(&[Action::PickUp, Action::PickUp, Action::PickUp]);
+    game.run_script(&[Action::UseItem(0), Action::UseItem(0)]);
+    assert_eq!(player_stats(&game).max_hp, max_hp + 40);
+
+    // a third ring takes the place of the first one
+    game.act(Action::UseItem(0));
+    let worn: Vec<Entity> = {
+        let equipped = game.world().read_storage::<Equipped>();
+        rings
+            .iter()
+            .filter(|ring| equipped.contains(**ring))
+            .copied()
+            .collect()
+    };
+    assert_eq!(worn.len(), 2);
+    assert_eq!(player_stats(&game).max_hp, max_hp + 40);
+
+    game.run_script(&[Action::RemoveItem(0), Action::RemoveItem(0)]);
+    assert_eq!(player_stats(&game).max_hp, max_hp);
+    assert!(player_stats(&game).hp <= max_hp);
+}
+
+/// The items on the floor at `pos` going by `name`.
+fn items_on_floor(game: &HeadlessGame, name: &str, pos: Point) -> Vec<Entity> {
+    let entities = game.world().entities();
+    let names = game.world().read_storage::<Name>();
+    let positions = game.world().read_storage::<Position>();
+    let items = game.world().read_storage::<Item>();
+    (&entities, &names, &positions, &items)
+        .join()
+        .filter(|(_, n, p, _)| n.name == name && p.x == pos.x && p.y == pos.y)
+        .map(|(entity, _, _, _)| entity)
+        .collect()
+}
+
+#[test]
+fn potions_stack_and_are_used_and_dropped_one_at_a_time() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let pos = position_of(&game, player(&game));
+    let potions: Vec<Entity> = (0..3)
+        .map(|_| spawn(&mut game, "Health Potion", pos))
+        .collect();
+
+    game.run_script(&[Action::PickUp, Action::PickUp, Action::PickUp]);
+    let stack = {
+        let backpack = game.world().read_storage::<InBackpack>();
+        let carried: Vec<Entity> = potions
+            .iter()
+            .filter(|potion| backpack.contains(**potion))
+            .copied()
+            .collect();
+        assert_eq!(carried.len(), 1);
+        carried[0]
+    };
+    let count = |game: &HeadlessGame| {
+        game.world()
+            .read_storage::<Stackable>()
+            .get(stack)
+            .unwrap()
+            .count
+    };
+    assert_eq!(count(&game), 3);
+
+    game.act(Action::UseItem(0));
+    assert!(game.world().entities().is_alive(stack));
+    assert_eq!(count(&game), 2);
+
+    game.act(Action::DropItem(0));
+    assert_eq!(count(&game), 1);
+    assert!(game.world().read_storage::<InBackpack>().contains(stack));
+    assert_eq!(items_on_floor(&game, "Health Potion", pos).len(), 1);
+
+    game.act(Action::DropItem(0));
+    assert_eq!(items_on_floor(&game, "Health Potion", pos).len(), 2);
+    assert!(!game.world().read_storage::<InBackpack>().contains(stack));
+}
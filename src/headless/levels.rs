@@ -0,0 +1,112 @@
+//! Going up and down the stairs.
+
+use super::{
+    fixtures::{
+        free_neighbour, player, position_of, spawn, stand_on, tile_under_player,
+        use_save_directory, SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{Monster, Position, Viewshed},
+    map::{Map, TileType},
+    map_indexing_system::MapIndexingSystem,
+    saveload_system,
+    visibility_system::VisibilitySystem,
+    RunState,
+};
+use rltk::Point;
+use specs::prelude::*;
+use std::{collections::HashSet, fs};
+
+/// Whether the player sees what a freshly indexed map lets them see,
+/// closed doors included.
+fn sight_is_up_to_date(game: &mut HeadlessGame) -> bool {
+    let player = player(game);
+    let seen = |game: &HeadlessGame| -> HashSet<Point> {
+        let viewsheds = game.world().read_storage::<Viewshed>();
+        viewsheds
+            .get(player)
+            .unwrap()
+            .visible_tiles
+            .iter()
+            .copied()
+            .collect()
+    };
+    let before = seen(game);
+    MapIndexingSystem {}.run_now(game.world());
+    game.world()
+        .write_storage::<Viewshed>()
+        .get_mut(player)
+        .unwrap()
+        .dirty = true;
+    VisibilitySystem {}.run_now(game.world());
+    before == seen(game)
+}
+
+#[test]
+fn closed_doors_block_the_first_sight_of_a_level() {
+    // a door in sight of the start, which used to be seen through
+    let mut game = HeadlessGame::new(8);
+    let save_dir = use_save_directory(&mut game, "first_sight");
+    assert!(sight_is_up_to_date(&mut game));
+
+    stand_on(&mut game, TileType::DownStairs);
+    game.act(Action::Descend);
+    assert!(sight_is_up_to_date(&mut game));
+    game.act(Action::Ascend);
+    assert!(sight_is_up_to_date(&mut game));
+
+    saveload_system::save_game(game.world_mut(), 0).unwrap();
+    saveload_system::load_game(game.world_mut(), 0).unwrap();
+    let (dx, dy) = free_neighbour(&game);
+    game.act(Action::Move { dx, dy });
+    assert!(sight_is_up_to_date(&mut game));
+    fs::remove_dir_all(save_dir).unwrap();
+}
+
+#[test]
+fn descend_the_stairs() {
+    let mut game = HeadlessGame::new(SEED);
+    stand_on(&mut game, TileType::DownStairs);
+
+    game.act(Action::Descend);
+
+    assert!(game.run_state() == RunState::AwaitingInput);
+    assert_eq!(game.world().fetch::<Map>().depth, 2);
+    assert!(tile_under_player(&game) == TileType::UpStairs);
+}
+
+#[test]
+fn levels_are_kept_when_going_back_up() {
+    let mut game = HeadlessGame::new(SEED);
+    let stairs = stand_on(&mut game, TileType::DownStairs);
+    let dagger = spawn(&mut game, "Dagger", stairs);
+    game.act(Action::Wait);
+    let monster_count = game.world().read_storage::<Monster>().join().count();
+    let revealed = game.world().fetch::<Map>().revealed_tiles.clone();
+
+    game.act(Action::Descend);
+    assert!(game
+        .world()
+        .read_storage::<Position>()
+        .get(dagger)
+        .is_none());
+    let level_2 = game.world().fetch::<Map>().tiles.clone();
+
+    game.act(Action::Ascend);
+    assert_eq!(game.world().fetch::<Map>().depth, 1);
+    assert_eq!(position_of(&game, player(&game)), stairs);
+    assert_eq!(game.world().fetch::<Map>().revealed_tiles, revealed);
+    let positions = game.world().read_storage::<Position>();
+    let dropped = positions.get(dagger).unwrap();
+    assert_eq!((dropped.x, dropped.y), (stairs.x, stairs.y));
+    let monsters = game.world().read_storage::<Monster>();
+    let here = (&monsters, &positions).join().count();
+    assert_eq!(here, monster_count);
+    drop((positions, monsters));
+
+    // and the level below is still the same one
+    game.act(Action::Descend);
+    assert!(game.world().fetch::<Map>().tiles == level_2);
+}
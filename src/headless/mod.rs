@@ -0,0 +1,257 @@
+use super::{init_state, map::Map, RunState, State};
+use fixtures::{player, player_stats, position_of, SEED};
+use rltk::{GameState, Point, Rltk, VirtualKeyCode, BACKEND, BACKEND_INTERNAL, RGB};
+use specs::prelude::*;
+
+mod combat;
+mod exploring;
+pub mod fixtures;
+mod interface;
+mod items;
+mod levels;
+mod monsters;
+mod saves;
+mod statuses;
+mod time;
+
+/// How many automatic ticks (player turn, monster turn, level change, auto
+/// walk step...) we run after a key before deciding the game is stuck.
+const MAX_TICKS_PER_INPUT: i32 = 10_000;
+
+const LETTERS: [VirtualKeyCode; 26] = [
+    VirtualKeyCode::A,
+    VirtualKeyCode::B,
+    VirtualKeyCode::C,
+    VirtualKeyCode::D,
+    VirtualKeyCode::E,
+    VirtualKeyCode::F,
+    VirtualKeyCode::G,
+    VirtualKeyCode::H,
+    VirtualKeyCode::I,
+    VirtualKeyCode::J,
+    VirtualKeyCode::K,
+    VirtualKeyCode::L,
+    VirtualKeyCode::M,
+    VirtualKeyCode::N,
+    VirtualKeyCode::O,
+    VirtualKeyCode::P,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::R,
+    VirtualKeyCode::S,
+    VirtualKeyCode::T,
+    VirtualKeyCode::U,
+    VirtualKeyCode::V,
+    VirtualKeyCode::W,
+    VirtualKeyCode::X,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+];
+
+/// Size of a tile on the headless screen, in pixels.
+const TILE_PIXELS: i32 = 8;
+
+/// High level player actions, turned into the keys `player_input` expects.
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    Move {
+        dx: i32,
+        dy: i32,
+    },
+    Wait,
+    Search,
+    /// Walks until there is nothing left to explore or a monster shows up.
+    Explore,
+    /// Walks to the stairs down.
+    TravelToStairs,
+    PickUp,
+    /// Uses the nth item of the inventory menu.
+    UseItem(usize),
+    DropItem(usize),
+    RemoveItem(usize),
+    Descend,
+    Ascend,
+    /// Saves in the first slot.
+    SaveAndQuit,
+}
+
+impl Action {
+    pub fn keys(&self) -> Vec<VirtualKeyCode> {
+        match *self {
+            Action::Move { dx, dy } => match (dx, dy) {
+                (0, -1) => vec![VirtualKeyCode::Up],
+                (0, 1) => vec![VirtualKeyCode::Down],
+                (-1, 0) => vec![VirtualKeyCode::Left],
+                (1, 0) => vec![VirtualKeyCode::Right],
+                (1, -1) => vec![VirtualKeyCode::Y],
+                (-1, -1) => vec![VirtualKeyCode::U],
+                (1, 1) => vec![VirtualKeyCode::N],
+                (-1, 1) => vec![VirtualKeyCode::B],
+                _ => panic!("Cannot move by ({}, {})", dx, dy),
+            },
+            Action::Wait => vec![VirtualKeyCode::S],
+            Action::Search => vec![VirtualKeyCode::X],
+            Action::Explore => vec![VirtualKeyCode::O],
+            Action::TravelToStairs => vec![VirtualKeyCode::T],
+            Action::PickUp => vec![VirtualKeyCode::G],
+            Action::UseItem(n) => vec![VirtualKeyCode::I, LETTERS[n]],
+            Action::DropItem(n) => vec![VirtualKeyCode::D, LETTERS[n]],
+            Action::RemoveItem(n) => vec![VirtualKeyCode::R, LETTERS[n]],
+            Action::Descend => vec![VirtualKeyCode::Period],
+            Action::Ascend => vec![VirtualKeyCode::Comma],
+            Action::SaveAndQuit => vec![VirtualKeyCode::Escape, VirtualKeyCode::Return],
+        }
+    }
+}
+
+/// Runs the game without a window: same world as `main`, scripted input,
+/// and a fixed frame time so that a seed and a script always give the same game.
+pub struct HeadlessGame {
+    pub gs: State,
+    ctx: Rltk,
+}
+
+impl HeadlessGame {
+    pub fn new(seed: u64) -> HeadlessGame {
+        let gs = init_state(seed);
+        *gs.ecs.write_resource::<RunState>() = RunState::PrePun;
+
+        let mut game = HeadlessGame {
+            gs,
+            ctx: headless_context(),
+        };
+        game.settle();
+        game
+    }
+
+    pub fn world(&self) -> &World {
+        &self.gs.ecs
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.gs.ecs
+    }
+
+    pub fn run_state(&self) -> RunState {
+        *self.gs.ecs.fetch::<RunState>()
+    }
+
+    /// Feeds one key to the game, then lets the turn play out.
+    pub fn press(&mut self, key: VirtualKeyCode) {
+        self.ctx.key = Some(key);
+        self.gs.tick(&mut self.ctx);
+        self.ctx.key = None;
+        self.settle();
+    }
+
+    /// Clicks on a tile of the map, then lets the turn play out.
+    pub fn click(&mut self, tile: Point) {
+        self.ctx.mouse_pos = (
+            tile.x * TILE_PIXELS + TILE_PIXELS / 2,
+            tile.y * TILE_PIXELS + TILE_PIXELS / 2,
+        );
+        self.ctx.left_click = true;
+        self.gs.tick(&mut self.ctx);
+        self.ctx.left_click = false;
+        self.settle();
+    }
+
+    pub fn act(&mut self, action: Action) {
+        for key in action.keys() {
+            self.press(key);
+        }
+    }
+
+    pub fn run_script(&mut self, actions: &[Action]) {
+        for action in actions.iter() {
+            self.act(*action);
+        }
+    }
+
+    /// Ticks until the game waits for input again.
+    fn settle(&mut self) {
+        let mut ticks = 0;
+        while self.is_running_by_itself() {
+            self.gs.tick(&mut self.ctx);
+            ticks += 1;
+            if ticks > MAX_TICKS_PER_INPUT {
+                panic!("Headless game never came back to waiting for input");
+            }
+        }
+    }
+
+    fn is_running_by_itself(&self) -> bool {
+        matches!(
+            self.run_state(),
+            RunState::PrePun
+                | RunState::AutoWalking
+                | RunState::PlayerTurn
+                | RunState::Ticking
+                | RunState::NextLevel
+                | RunState::PreviousLevel
+        )
+    }
+}
+
+/// A context drawing into an in-memory console, nothing is ever displayed.
+fn headless_context() -> Rltk {
+    {
+        // for clicks to land on the right tile
+        let mut backend = BACKEND.lock();
+        backend.screen_scaler.available_width = (80 * TILE_PIXELS) as u32;
+        backend.screen_scaler.available_height = (50 * TILE_PIXELS) as u32;
+    }
+    {
+        let mut backend = BACKEND_INTERNAL.lock();
+        if backend.consoles.is_empty() {
+            backend.consoles.push(rltk::DisplayConsole {
+                console: rltk::SimpleConsole::init(80, 50),
+                shader_index: 0,
+                font_index: 0,
+            });
+        }
+    }
+
+    Rltk {
+        width_pixels: 640,
+        height_pixels: 400,
+        original_width_pixels: 640,
+        original_height_pixels: 400,
+        fps: 60.0,
+        frame_time_ms: 16.0,
+        active_console: 0,
+        key: None,
+        mouse_pos: (0, 0),
+        left_click: false,
+        shift: false,
+        control: false,
+        alt: false,
+        web_button: None,
+        quitting: false,
+        post_scanlines: false,
+        post_screenburn: false,
+        screen_burn_color: RGB::named(rltk::BLACK),
+        mouse_visible: false,
+    }
+}
+
+#[test]
+fn same_seed_same_game() {
+    let script = [
+        Action::Move { dx: 1, dy: 0 },
+        Action::Move { dx: 0, dy: 1 },
+        Action::Wait,
+        Action::Move { dx: -1, dy: 0 },
+    ];
+
+    let mut first = HeadlessGame::new(SEED);
+    first.run_script(&script);
+    let mut second = HeadlessGame::new(SEED);
+    second.run_script(&script);
+
+    assert!(first.world().fetch::<Map>().tiles == second.world().fetch::<Map>().tiles);
+    assert_eq!(
+        position_of(&first, player(&first)),
+        position_of(&second, player(&second))
+    );
+    assert_eq!(player_stats(&first).hp, player_stats(&second).hp);
+}
@@ -0,0 +1,368 @@
+//! What monsters decide to do, on their own and with their factions.
+
+use super::{
+    fixtures::{
+        clear_monsters, far_away, in_step, player, position_of, spawn, stand_before, teleport,
+        text, SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{
+        AiState, Ammunition, Brain, CombatStats, InBackpack, Monster, Name, Position, SpellBook,
+        SpellEffect,
+    },
+    damage_system,
+    flow_map_system::PlayerFlowMap,
+    gamelog::GameLog,
+    map::{Map, TileType},
+};
+use rltk::Point;
+use specs::prelude::*;
+
+fn brain_state(game: &HeadlessGame, monster: Entity) -> AiState {
+    game.world()
+        .read_storage::<Brain>()
+        .get(monster)
+        .unwrap()
+        .state
+}
+
+#[test]
+fn hurt_goblins_run_away() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let goblin = spawn(&mut game, "Goblin", Point::new(start.x + 2, start.y));
+    in_step(&mut game, goblin);
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(goblin)
+        .unwrap()
+        .hp = 4;
+
+    game.act(Action::Wait);
+
+    assert_eq!(brain_state(&game, goblin), AiState::Fleeing);
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(start, position_of(&game, goblin));
+    assert!(distance > 2.0);
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "Goblin turns to flee!"));
+}
+
+#[test]
+fn orcs_fight_to_the_death() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 2]);
+    let orc = spawn(&mut game, "Orc", Point::new(start.x + 1, start.y));
+    in_step(&mut game, orc);
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(orc)
+        .unwrap()
+        .hp = 1;
+
+    game.act(Action::Wait);
+
+    assert!(matches!(brain_state(&game, orc), AiState::Chasing { .. }));
+    let log = game.world().fetch::<GameLog>();
+    assert!(log.entries().any(|entry| text(entry).starts_with("Orc")));
+}
+
+#[test]
+fn monsters_search_where_they_last_saw_the_player() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let goblin = spawn(&mut game, "Goblin", Point::new(start.x + 3, start.y));
+    in_step(&mut game, goblin);
+    game.act(Action::Wait);
+    assert_eq!(
+        brain_state(&game, goblin),
+        AiState::Chasing {
+            x: start.x,
+            y: start.y
+        }
+    );
+
+    let hideout = far_away(&game, start);
+    teleport(&mut game, hideout);
+    game.act(Action::Wait);
+    assert!(matches!(
+        brain_state(&game, goblin),
+        AiState::Searching { x, y, .. } if Point::new(x, y) == start
+    ));
+    game.act(Action::Wait);
+    assert_eq!(position_of(&game, goblin), start);
+    // nobody there, it gives up
+    game.act(Action::Wait);
+    assert_eq!(brain_state(&game, goblin), AiState::Idle);
+}
+
+#[test]
+fn relentless_orcs_track_the_player_down() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let orc = spawn(&mut game, "Orc", Point::new(start.x + 3, start.y));
+    in_step(&mut game, orc);
+    game.act(Action::Wait);
+
+    let hideout = far_away(&game, start);
+    teleport(&mut game, hideout);
+    for _ in 0..6 {
+        game.act(Action::Wait);
+    }
+
+    // well past where it last saw the player
+    let distance = |from: Point| rltk::DistanceAlg::Pythagoras.distance2d(from, hideout);
+    assert!(distance(position_of(&game, orc)) < distance(start) - 2.0);
+    assert!(matches!(brain_state(&game, orc), AiState::Searching { .. }));
+}
+
+#[test]
+fn archers_shoot_from_a_distance() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 5]);
+    let archer = spawn(&mut game, "Goblin Archer", Point::new(start.x + 4, start.y));
+    in_step(&mut game, archer);
+
+    game.act(Action::Wait);
+
+    assert_eq!(position_of(&game, archer), Point::new(start.x + 4, start.y));
+    // too far for its fists, so anything it did was a shot
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry).starts_with("Goblin Archer")));
+    let arrows = game.world().read_storage::<Ammunition>();
+    let backpack = game.world().read_storage::<InBackpack>();
+    let (ammo, _) = (&arrows, &backpack)
+        .join()
+        .find(|(_, carried)| carried.owner == archer)
+        .unwrap();
+    assert_eq!(ammo.count, 11);
+}
+
+#[test]
+fn archers_back_away_from_the_player() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let archer = spawn(&mut game, "Goblin Archer", Point::new(start.x + 1, start.y));
+    in_step(&mut game, archer);
+
+    game.act(Action::Wait);
+
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(start, position_of(&game, archer));
+    assert!(distance > 1.5);
+}
+
+#[test]
+fn shamans_heal_hurt_allies() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let shaman = spawn(&mut game, "Orc Shaman", Point::new(start.x + 3, start.y));
+    in_step(&mut game, shaman);
+    let orc = spawn(&mut game, "Orc", Point::new(start.x + 5, start.y));
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(orc)
+        .unwrap()
+        .hp = 2;
+
+    game.act(Action::Wait);
+
+    let hp = game
+        .world()
+        .read_storage::<CombatStats>()
+        .get(orc)
+        .unwrap()
+        .hp;
+    assert_eq!(hp, 10);
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "Orc Shaman casts Mend on Orc, healing 8 hp"));
+}
+
+#[test]
+fn spells_wait_for_their_cooldown() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let shaman = spawn(&mut game, "Orc Shaman", Point::new(start.x + 3, start.y));
+    in_step(&mut game, shaman);
+    game.world()
+        .write_storage::<SpellBook>()
+        .get_mut(shaman)
+        .unwrap()
+        .spells
+        .retain(|spell| matches!(spell.effect, SpellEffect::Bolt { .. }));
+
+    // cast on the first turn, then three turns later
+    for _ in 0..6 {
+        game.act(Action::Wait);
+    }
+
+    let log = game.world().fetch::<GameLog>();
+    let casts = log
+        .entries()
+        .filter(|entry| text(entry).starts_with("Orc Shaman casts Firebolt"))
+        .count();
+    assert_eq!(casts, 2);
+}
+
+#[test]
+fn shamans_call_for_help() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let shaman = spawn(&mut game, "Orc Shaman", Point::new(start.x + 3, start.y));
+    in_step(&mut game, shaman);
+
+    game.act(Action::Wait);
+
+    let names = game.world().read_storage::<Name>();
+    let monsters = game.world().read_storage::<Monster>();
+    let goblins = (&names, &monsters)
+        .join()
+        .filter(|(name, _)| name.name == "Goblin")
+        .count();
+    assert_eq!(goblins, 1);
+}
+
+#[test]
+fn dead_monsters_drop_their_belongings() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 3]);
+    let archer = spawn(&mut game, "Goblin Archer", Point::new(start.x + 2, start.y));
+    game.world()
+        .write_storage::<CombatStats>()
+        .get_mut(archer)
+        .unwrap()
+        .hp = 0;
+
+    damage_system::delete_the_dead(game.world_mut());
+
+    let names = game.world().read_storage::<Name>();
+    let positions = game.world().read_storage::<Position>();
+    let dropped: Vec<&str> = (&names, &positions)
+        .join()
+        .filter(|(_, pos)| pos.x == start.x + 2 && pos.y == start.y)
+        .map(|(name, _)| name.name.as_str())
+        .collect();
+    assert!(dropped.contains(&"Shortbow"));
+    assert!(dropped.contains(&"Arrows"));
+}
+
+#[test]
+fn bumping_a_neutral_swaps_places() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 3]);
+    let miner = spawn(&mut game, "Lost Miner", Point::new(start.x + 1, start.y));
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+
+    assert_eq!(
+        position_of(&game, player(&game)),
+        Point::new(start.x + 1, start.y)
+    );
+    assert_eq!(position_of(&game, miner), start);
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "You swap places with the Lost Miner."));
+}
+
+#[test]
+fn monsters_fight_other_factions() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 5]);
+    let goblin = spawn(&mut game, "Goblin", Point::new(start.x + 3, start.y));
+    in_step(&mut game, goblin);
+    let rat = spawn(&mut game, "Giant Rat", Point::new(start.x + 4, start.y));
+    in_step(&mut game, rat);
+
+    game.act(Action::Wait);
+
+    // the rat is closer than the player
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry).starts_with("Goblin") && text(entry).contains("Giant Rat")));
+}
+
+#[test]
+fn townsfolk_run_from_monsters() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let miner = spawn(&mut game, "Lost Miner", Point::new(start.x + 3, start.y));
+    in_step(&mut game, miner);
+    spawn(&mut game, "Goblin", Point::new(start.x + 5, start.y));
+
+    game.act(Action::Wait);
+
+    assert_eq!(brain_state(&game, miner), AiState::Fleeing);
+    assert!(position_of(&game, miner).x < start.x + 3);
+}
+
+#[test]
+fn pets_follow_the_player() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 6]);
+    let dog = spawn(&mut game, "Stray Dog", Point::new(start.x + 5, start.y));
+    in_step(&mut game, dog);
+
+    game.act(Action::Wait);
+
+    assert_eq!(position_of(&game, dog), Point::new(start.x + 4, start.y));
+}
+
+#[test]
+fn pets_defend_the_player() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 4]);
+    let dog = spawn(&mut game, "Stray Dog", Point::new(start.x + 1, start.y));
+    in_step(&mut game, dog);
+    spawn(&mut game, "Goblin", Point::new(start.x + 2, start.y));
+
+    game.act(Action::Wait);
+
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry).starts_with("Stray Dog") && text(entry).contains("Goblin")));
+}
+
+#[test]
+fn the_flow_map_leads_to_the_player() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    game.act(Action::Wait);
+
+    let start = position_of(&game, player(&game));
+    let hideout = far_away(&game, start);
+    let flow = game.world().fetch::<PlayerFlowMap>();
+    let map = game.world().fetch::<Map>();
+    let mut idx = map.get_index_at(hideout.x, hideout.y);
+    let mut steps = 0;
+    while let Some(next) = flow.step_toward(&map, idx) {
+        idx = next;
+        steps += 1;
+        assert!(steps < 20);
+    }
+    let (x, y) = map.get_xy_from_idx(idx);
+    assert!(rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x, y), start) < 1.5);
+}
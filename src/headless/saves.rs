@@ -0,0 +1,133 @@
+//! Saving and loading from within the game.
+
+use super::{
+    fixtures::{
+        free_neighbour, player, player_stats, position_of, spawn, stand_on, use_save_directory,
+        SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{CombatStats, InflictsTeleportsSymetrically, Monster, Name, Position},
+    map::{Map, TileType},
+    saveload_system, GameMode, RunState, TurnCounter,
+};
+use specs::prelude::*;
+use std::fs;
+
+/// Whether there are offensive teleport scrolls, and they all still teleport.
+fn teleport_scrolls_work(game: &HeadlessGame) -> bool {
+    let entities = game.world().entities();
+    let names = game.world().read_storage::<Name>();
+    let teleports = game.world().read_storage::<InflictsTeleportsSymetrically>();
+    let scrolls: Vec<bool> = (&entities, &names)
+        .join()
+        .filter(|(_, name)| name.name == "Offensive Teleport Scroll")
+        .map(|(scroll, _)| teleports.contains(scroll))
+        .collect();
+    !scrolls.is_empty() && scrolls.iter().all(|works| *works)
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let mut game = HeadlessGame::new(SEED);
+    let save_dir = use_save_directory(&mut game, "round_trip");
+    game.act(Action::Move { dx: 1, dy: 0 });
+    let pos = position_of(&game, player(&game));
+    let hp = player_stats(&game).hp;
+    let depth = game.world().fetch::<Map>().depth;
+    let turns = game.world().fetch::<TurnCounter>().turns;
+    let monster_count = game.world().read_storage::<Monster>().join().count();
+    spawn(&mut game, "Offensive Teleport Scroll", pos);
+
+    game.act(Action::SaveAndQuit);
+    assert!(matches!(game.run_state(), RunState::MainMenu { .. }));
+
+    let metadata = saveload_system::read_slot(game.world(), 0)
+        .unwrap()
+        .unwrap()
+        .metadata
+        .unwrap();
+    assert_eq!(
+        (metadata.depth, metadata.hp, metadata.turns, metadata.seed),
+        (depth, hp, turns, SEED)
+    );
+
+    saveload_system::load_game(game.world_mut(), 0).unwrap();
+    assert_eq!(position_of(&game, player(&game)), pos);
+    assert_eq!(player_stats(&game).hp, hp);
+    assert_eq!(game.world().fetch::<Map>().depth, depth);
+    assert_eq!(game.world().fetch::<TurnCounter>().turns, turns);
+    assert_eq!(
+        game.world().read_storage::<Monster>().join().count(),
+        monster_count
+    );
+    let names = game.world().read_storage::<Name>();
+    let player = player(&game);
+    assert_eq!(names.get(player).unwrap().name, "Player");
+    assert!(teleport_scrolls_work(&game));
+    assert!(saveload_system::read_slot(game.world(), 0).is_some());
+    fs::remove_dir_all(save_dir).unwrap();
+}
+
+#[test]
+fn stored_levels_are_saved() {
+    let mut game = HeadlessGame::new(SEED);
+    let save_dir = use_save_directory(&mut game, "stored_levels");
+    let stairs = stand_on(&mut game, TileType::DownStairs);
+    spawn(&mut game, "Dagger", stairs);
+    game.act(Action::Descend);
+
+    game.act(Action::SaveAndQuit);
+    saveload_system::load_game(game.world_mut(), 0).unwrap();
+    *game.world_mut().write_resource::<RunState>() = RunState::AwaitingInput;
+    assert_eq!(game.world().fetch::<Map>().depth, 2);
+
+    game.act(Action::Ascend);
+    assert_eq!(game.world().fetch::<Map>().depth, 1);
+    let names = game.world().read_storage::<Name>();
+    let positions = game.world().read_storage::<Position>();
+    assert!((&names, &positions)
+        .join()
+        .any(|(name, pos)| name.name == "Dagger" && pos.x == stairs.x && pos.y == stairs.y));
+    drop((names, positions));
+    fs::remove_dir_all(save_dir).unwrap();
+}
+
+#[test]
+fn permadeath_saves_are_consumed() {
+    let mut game = HeadlessGame::new(SEED);
+    let save_dir = use_save_directory(&mut game, "permadeath");
+    game.world_mut().fetch_mut::<GameMode>().permadeath = true;
+
+    game.act(Action::SaveAndQuit);
+    let metadata = saveload_system::read_slot(game.world(), 0)
+        .unwrap()
+        .unwrap()
+        .metadata
+        .unwrap();
+    assert!(metadata.permadeath);
+
+    // loading deletes the save, and the mode comes back with it
+    game.world_mut().fetch_mut::<GameMode>().permadeath = false;
+    saveload_system::load_game(game.world_mut(), 0).unwrap();
+    assert!(saveload_system::read_slot(game.world(), 0).is_none());
+    assert!(game.world().fetch::<GameMode>().permadeath);
+
+    // dying deletes the run's save too
+    *game.world_mut().write_resource::<RunState>() = RunState::AwaitingInput;
+    saveload_system::save_game(game.world_mut(), 0).unwrap();
+    let player = player(&game);
+    game.world_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 0;
+    let (dx, dy) = free_neighbour(&game);
+    game.act(Action::Move { dx, dy });
+    assert!(game.run_state() == RunState::GameOver);
+    game.act(Action::Wait);
+    assert!(saveload_system::read_slot(game.world(), 0).is_none());
+    assert!(matches!(game.run_state(), RunState::MainMenu { .. }));
+    fs::remove_dir_all(save_dir).unwrap();
+}
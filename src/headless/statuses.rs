@@ -0,0 +1,148 @@
+//! Statuses and hunger wearing on creatures over the turns.
+
+use super::{
+    fixtures::{
+        aim_at, clear_monsters, free_neighbour, player, player_stats, position_of, spawn, text,
+        SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{
+        CombatStats, Energy, HungerClock, HungerState, StatusEffect, StatusEffects, StatusKind,
+    },
+    gamelog::GameLog,
+    initiative_system::NORMAL_SPEED,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+#[test]
+fn poison_hurts_every_turn_then_wears_off() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let player = player(&game);
+    {
+        let mut statuses = StatusEffects::default();
+        statuses.add(StatusEffect::new(StatusKind::Poison, 3));
+        statuses.add(StatusEffect::new(StatusKind::Poison, 3));
+        game.world_mut()
+            .write_storage::<StatusEffects>()
+            .insert(player, statuses)
+            .unwrap();
+    }
+
+    for _ in 0..3 {
+        game.act(Action::Wait);
+    }
+
+    let log = game.world().fetch::<GameLog>();
+    let hurt = log
+        .entries()
+        .filter(|entry| text(entry) == "You are poisoned, you suffer 2 hp.")
+        .count();
+    assert_eq!(hurt, 3);
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "You are no longer poisoned."));
+    let statuses = game.world().read_storage::<StatusEffects>();
+    assert!(statuses.get(player).unwrap().effects.is_empty());
+}
+
+#[test]
+fn a_confused_goblin_loses_its_turns() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    let goblin = spawn(&mut game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+    game.world()
+        .write_storage::<Energy>()
+        .get_mut(goblin)
+        .unwrap()
+        .speed = NORMAL_SPEED;
+    spawn(&mut game, "Confusion Scroll", pos);
+    game.act(Action::PickUp);
+    let goblin_attacks = |game: &HeadlessGame| {
+        let log = game.world().fetch::<GameLog>();
+        log.entries()
+            .filter(|entry| text(entry).starts_with("Goblin"))
+            .count()
+    };
+    let attacks_before = goblin_attacks(&game);
+
+    game.act(Action::UseItem(0));
+    aim_at(&mut game, Point::new(pos.x + dx, pos.y + dy));
+    let statuses = game.world().read_storage::<StatusEffects>();
+    assert!(statuses.get(goblin).unwrap().has(StatusKind::Confusion));
+    drop(statuses);
+
+    // the scroll confuses for 4 turns, counting the one it is read in
+    for _ in 0..3 {
+        game.act(Action::Wait);
+    }
+    assert_eq!(goblin_attacks(&game), attacks_before);
+    game.act(Action::Wait);
+    assert_eq!(goblin_attacks(&game), attacks_before + 1);
+}
+
+fn set_hunger(game: &mut HeadlessGame, state: HungerState, duration: i32) {
+    let player = player(game);
+    game.world_mut()
+        .write_storage::<HungerClock>()
+        .insert(player, HungerClock { state, duration })
+        .unwrap();
+}
+
+fn hunger(game: &HeadlessGame) -> HungerState {
+    let player = player(game);
+    game.world()
+        .read_storage::<HungerClock>()
+        .get(player)
+        .unwrap()
+        .state
+}
+
+#[test]
+fn hunger_stops_healing_and_starving_hurts() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let player = player(&game);
+    game.world_mut()
+        .write_storage::<CombatStats>()
+        .get_mut(player)
+        .unwrap()
+        .hp = 100;
+
+    game.act(Action::Wait);
+    assert_eq!(player_stats(&game).hp, 101);
+
+    set_hunger(&mut game, HungerState::Hungry, 2);
+    game.act(Action::Wait);
+    assert!(hunger(&game) == HungerState::Hungry);
+    assert_eq!(player_stats(&game).hp, 101);
+
+    game.act(Action::Wait);
+    assert!(hunger(&game) == HungerState::Starving);
+    game.act(Action::Wait);
+    game.act(Action::Wait);
+    assert_eq!(player_stats(&game).hp, 99);
+}
+
+#[test]
+fn eating_rations_makes_you_well_fed() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    set_hunger(&mut game, HungerState::Hungry, 50);
+    let pos = position_of(&game, player(&game));
+    let rations = spawn(&mut game, "Rations", pos);
+
+    game.run_script(&[Action::PickUp, Action::UseItem(0)]);
+
+    assert!(hunger(&game) == HungerState::WellFed);
+    assert!(!game.world().entities().is_alive(rations));
+    let log = game.world().fetch::<GameLog>();
+    assert!(log
+        .entries()
+        .any(|entry| text(entry) == "You eat the Rations."));
+}
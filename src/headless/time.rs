@@ -0,0 +1,188 @@
+//! Who acts when: speed, turn costs and burdens.
+
+use super::{
+    fixtures::{
+        arm_with_shortbow, clear_monsters, far_away, fire_at, free_neighbour, in_step, player,
+        position_of, spawn, stand_before, stand_on, text, SEED,
+    },
+    Action, HeadlessGame,
+};
+use crate::{
+    components::{Attributes, Brain, Energy, MyTurn},
+    gamelog::GameLog,
+    gamesystem::carry_capacity,
+    initiative_system::{AIMED_TURN_COST, NORMAL_SPEED, TURN_COST},
+    map::TileType,
+    raws::{spawn_named_entity, SpawnType, RAWS},
+    RunState,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+/// How many times a goblin next to the player attacks, hit or miss, while the
+/// player waits.
+fn goblin_attacks(player_speed: i32, goblin_speed: i32, waits: usize) -> usize {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    goblin_attacks_in(&mut game, player_speed, goblin_speed, waits)
+}
+
+fn goblin_attacks_in(
+    game: &mut HeadlessGame,
+    player_speed: i32,
+    goblin_speed: i32,
+    waits: usize,
+) -> usize {
+    let (dx, dy) = free_neighbour(game);
+    let pos = position_of(game, player(game));
+    let goblin = spawn(game, "Goblin", Point::new(pos.x + dx, pos.y + dy));
+    let player = player(game);
+    {
+        let mut energy = game.world().write_storage::<Energy>();
+        energy.get_mut(player).unwrap().speed = player_speed;
+        energy.get_mut(goblin).unwrap().speed = goblin_speed;
+    }
+
+    for _ in 0..waits {
+        game.act(Action::Wait);
+    }
+
+    let log = game.world().fetch::<GameLog>();
+    // not the fights monsters had among themselves before the test began
+    log.entries()
+        .filter(|entry| text(entry).starts_with("Goblin") && text(entry).contains("Player"))
+        .count()
+}
+
+#[test]
+fn faster_creatures_act_more_often() {
+    // on a tie the player goes first, so the goblin's last turn is still to come
+    assert_eq!(goblin_attacks(10, 10, 6), 5);
+    assert_eq!(goblin_attacks(10, 20, 6), 11);
+    // a hasted player gets two turns for each of the goblin's
+    assert_eq!(goblin_attacks(20, 10, 6), 2);
+}
+
+#[test]
+fn carrying_too_much_slows_the_player_down() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let player = player(&game);
+    let might = game
+        .world()
+        .read_storage::<Attributes>()
+        .get(player)
+        .unwrap()
+        .might;
+    let shields = (carry_capacity(might) / 8.0) as usize + 1;
+    for _ in 0..shields {
+        let raws = RAWS.lock().unwrap();
+        spawn_named_entity(
+            &raws,
+            game.world_mut().create_entity(),
+            "Tower Shield",
+            SpawnType::Carried { by: player },
+        );
+    }
+    game.world_mut().maintain();
+
+    // at half speed, like a player of speed 10 against a goblin of speed 20
+    assert_eq!(
+        goblin_attacks_in(&mut game, NORMAL_SPEED, NORMAL_SPEED, 6),
+        11
+    );
+}
+
+/// The energy the player pays for what `act` makes them do, as gained in
+/// the meantime by a bystander that never gets to act.
+fn energy_spent_on(game: &mut HeadlessGame, act: impl FnOnce(&mut HeadlessGame)) -> i32 {
+    let at = far_away(game, position_of(game, player(game)));
+    let bystander = spawn(game, "Goblin", at);
+    let player = player(game);
+    let start = -10 * TURN_COST;
+    {
+        let mut energy = game.world().write_storage::<Energy>();
+        let ready = Energy {
+            speed: NORMAL_SPEED,
+            current: TURN_COST,
+        };
+        energy.insert(player, ready).unwrap();
+        let never_ready = Energy {
+            speed: NORMAL_SPEED,
+            current: start,
+        };
+        energy.insert(bystander, never_ready).unwrap();
+    }
+    act(game);
+    let gained = game
+        .world()
+        .read_storage::<Energy>()
+        .get(bystander)
+        .unwrap()
+        .current
+        - start;
+    game.world_mut().delete_entity(bystander).unwrap();
+    gained
+}
+
+#[test]
+fn taking_aim_costs_more_than_a_step() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let start = stand_before(&mut game, &[TileType::Floor; 5]);
+    arm_with_shortbow(&mut game);
+
+    assert_eq!(
+        energy_spent_on(&mut game, |game| game.act(Action::Wait)),
+        TURN_COST
+    );
+    let target = Point::new(start.x + 4, start.y);
+    assert_eq!(
+        energy_spent_on(&mut game, |game| fire_at(game, target)),
+        AIMED_TURN_COST
+    );
+}
+
+#[test]
+fn monsters_left_behind_mid_turn_dont_stop_time() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let stairs = stand_on(&mut game, TileType::DownStairs);
+    let at = far_away(&game, stairs);
+    // in step with the player, so it is still to act when the player leaves
+    let orc = spawn(&mut game, "Orc", at);
+    {
+        let player = player(&game);
+        let mut energy = game.world().write_storage::<Energy>();
+        let players = energy.get(player).unwrap().clone();
+        energy.insert(orc, players).unwrap();
+    }
+    game.act(Action::Wait);
+
+    game.act(Action::Descend);
+    let (dx, dy) = free_neighbour(&game);
+    let pos = position_of(&game, player(&game));
+    game.act(Action::Move { dx, dy });
+    assert!(game.run_state() == RunState::AwaitingInput);
+    assert_eq!(
+        position_of(&game, player(&game)),
+        Point::new(pos.x + dx, pos.y + dy)
+    );
+}
+
+#[test]
+fn turns_nothing_takes_dont_stop_time() {
+    let mut game = HeadlessGame::new(SEED);
+    clear_monsters(&mut game);
+    let at = far_away(&game, position_of(&game, player(&game)));
+    let orc = spawn(&mut game, "Orc", at);
+    in_step(&mut game, orc);
+    // no mind to take its turn with
+    game.world().write_storage::<Brain>().remove(orc);
+
+    for _ in 0..3 {
+        game.act(Action::Wait);
+    }
+    assert!(game.run_state() == RunState::AwaitingInput);
+    assert!(!game.world().read_storage::<MyTurn>().contains(orc));
+}
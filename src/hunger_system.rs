@@ -0,0 +1,85 @@
+use super::{
+    effects::{EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
+    HungerClock, HungerState, RunState,
+};
+use specs::prelude::*;
+
+/// Turns spent in each state before moving on to the next one.
+pub const WELL_FED_TURNS: i32 = 20;
+const NORMAL_TURNS: i32 = 200;
+const HUNGRY_TURNS: i32 = 200;
+
+/// Makes hunger clocks tick once per player turn. Starving hurts every turn.
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, HungerClock>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut hunger_clock, player_entity, runstate, mut effects, mut log) = data;
+
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, clock) in (&entities, &mut hunger_clock).join() {
+            clock.duration -= 1;
+            if clock.duration > 0 {
+                continue;
+            }
+
+            let is_player = entity == *player_entity;
+            match clock.state {
+                HungerState::WellFed => {
+                    clock.state = HungerState::Normal;
+                    clock.duration = NORMAL_TURNS;
+                    if is_player {
+                        Logger::new(LogCategory::System)
+                            .append("You are no longer well fed.")
+                            .log(&mut log);
+                    }
+                }
+                HungerState::Normal => {
+                    clock.state = HungerState::Hungry;
+                    clock.duration = HUNGRY_TURNS;
+                    if is_player {
+                        Logger::new(LogCategory::System)
+                            .color(rltk::RGB::named(rltk::ORANGE), "You are hungry.")
+                            .log(&mut log);
+                    }
+                }
+                HungerState::Hungry => {
+                    clock.state = HungerState::Starving;
+                    clock.duration = 0;
+                    if is_player {
+                        Logger::new(LogCategory::System)
+                            .color(rltk::RGB::named(rltk::RED), "You are starving!")
+                            .log(&mut log);
+                    }
+                }
+                HungerState::Starving => {
+                    if is_player {
+                        Logger::new(LogCategory::System)
+                            .append("Your hunger pangs are getting painful, you suffer")
+                            .damage(1)
+                            .append("hp.")
+                            .log(&mut log);
+                    }
+                    effects.push(
+                        Some(entity),
+                        EffectType::Damage { amount: 1 },
+                        Targets::Single { target: entity },
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use super::{
+    gamesystem::{carried_weight, carry_capacity, equipment_bonus},
+    Ammunition, Attributes, Energy, EquipmentBonus, Equipped, InBackpack, Item, MyTurn, Position,
+    Stackable, StatusEffects,
+};
+use specs::prelude::*;
+
+/// Energy an ordinary action costs.
+pub const TURN_COST: i32 = 100;
+/// Energy a shot or a spell costs, taking aim is slower than a swing or a step.
+pub const AIMED_TURN_COST: i32 = 150;
+/// Energy gained per tick at normal speed, one action every ten ticks.
+pub const NORMAL_SPEED: i32 = 10;
+
+/// Advances game time by one tick: everything on the level gains energy, and
+/// whoever has enough to act gets `MyTurn`. Time stands still while someone
+/// still has to take their turn. A creature carrying more than its might
+/// allows has to save up another turn's worth of energy and spend it on the
+/// weight, acting half as often; what it carries is only weighed once it has
+/// the energy to act.
+pub struct InitiativeSystem {}
+
+impl<'a> System<'a> for InitiativeSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, MyTurn>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, EquipmentBonus>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, Attributes>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Stackable>,
+        ReadStorage<'a, Ammunition>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut energy,
+            mut my_turn,
+            positions,
+            equipped,
+            bonuses,
+            statuses,
+            attributes,
+            items,
+            backpack,
+            stacks,
+            ammunition,
+        ) = data;
+
+        // only those on the level, the others can't take their turn
+        if (&my_turn, &positions).join().next().is_some() {
+            return;
+        }
+
+        for (entity, energy, _pos) in (&entities, &mut energy, &positions).join() {
+            let speed = energy.speed + equipment_bonus(entity, &equipped, &bonuses).speed;
+            energy.current += match statuses.get(entity) {
+                Some(statuses) => statuses.speed(speed),
+                None => speed,
+            };
+            if energy.current < TURN_COST {
+                continue;
+            }
+            let burdened = attributes.get(entity).is_some_and(|attributes| {
+                carried_weight(entity, &items, &backpack, &equipped, &stacks, &ammunition)
+                    > carry_capacity(attributes.might)
+            });
+            if burdened {
+                if energy.current < 2 * TURN_COST {
+                    continue;
+                }
+                energy.current -= TURN_COST;
+            }
+            my_turn
+                .insert(entity, MyTurn {})
+                .expect("Unable to insert turn");
+        }
+    }
+}
+
+/// Ends the turn of `entity`, paying `cost` energy for what it did.
+pub fn end_turn(
+    entity: Entity,
+    cost: i32,
+    energy: &mut WriteStorage<Energy>,
+    my_turn: &mut WriteStorage<MyTurn>,
+) {
+    if let Some(energy) = energy.get_mut(entity) {
+        energy.current -= cost;
+    }
+    my_turn.remove(entity);
+}
+
+/// Ends the turns nothing took, of creatures left without a mind to act
+/// with, as if they waited. Otherwise time would stand still for good.
+pub fn pass_unclaimed_turns(ecs: &World) {
+    let player = *ecs.fetch::<Entity>();
+    let unclaimed: Vec<Entity> = (
+        &ecs.entities(),
+        &ecs.read_storage::<MyTurn>(),
+        &ecs.read_storage::<Position>(),
+    )
+        .join()
+        .map(|(entity, _, _)| entity)
+        .filter(|entity| *entity != player)
+        .collect();
+    let mut energy = ecs.write_storage::<Energy>();
+    let mut my_turn = ecs.write_storage::<MyTurn>();
+    for entity in unclaimed {
+        end_turn(entity, TURN_COST, &mut energy, &mut my_turn);
+    }
+}
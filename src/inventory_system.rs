@@ -2,14 +2,14 @@ use specs::prelude::*;
 
 use crate::{
     components::{
-        AreaOfEffect, CombatStats, Confusion, Consumable, Equippable, Equipped, InBackpack,
-        InflictsDamage, InflictsTeleportsSymetrically, Name, Position, ProvidesHealing,
-        SufferDamage, TeleportsSymetrically, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem,
+        Ammunition, AreaOfEffect, CombatStats, EquipmentBonus, Equippable, Equipped, InBackpack,
+        Name, Position, Stackable, Viewshed, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem,
         WantsToUseItem,
     },
-    gamelog::GameLog,
+    effects::{EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
     map::Map,
-    particle_system::ParticleBuilder,
+    raws::{spawn_named_item, SpawnType, RAWS},
 };
 
 pub struct ItemCollectionSystem {}
@@ -18,32 +18,83 @@ impl<'a> System<'a> for ItemCollectionSystem {
     type SystemData = (
         ReadExpect<'a, Entity>,
         WriteExpect<'a, GameLog>,
+        Entities<'a>,
         WriteStorage<'a, WantsToPickupItem>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        WriteStorage<'a, Ammunition>,
+        WriteStorage<'a, Stackable>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            equipped,
+            mut ammunition,
+            mut stacks,
+        ) = data;
 
         for pickup in wants_pickup.join() {
-            positions.remove(pickup.item);
-            backpack
-                .insert(
-                    pickup.item,
-                    InBackpack {
-                        owner: pickup.collected_by,
-                    },
-                )
-                .expect("Unable to insert backpack entry");
+            let item_name = &names.get(pickup.item).unwrap().name;
+
+            // ammunition and stackable items join a stack of the same kind the
+            // collector already carries
+            let count = ammunition
+                .get(pickup.item)
+                .map(|ammo| ammo.count)
+                .or_else(|| stacks.get(pickup.item).map(|stack| stack.count));
+            let stack = count.and_then(|count| {
+                (&entities, &names)
+                    .join()
+                    .find(|(other, other_name)| {
+                        *other != pickup.item
+                            && other_name.name == *item_name
+                            && (ammunition.contains(*other) || stacks.contains(*other))
+                            && (backpack
+                                .get(*other)
+                                .is_some_and(|b| b.owner == pickup.collected_by)
+                                || equipped
+                                    .get(*other)
+                                    .is_some_and(|e| e.owner == pickup.collected_by))
+                    })
+                    .map(|(other, _)| (other, count))
+            });
+
+            if let Some((stack, count)) = stack {
+                if let Some(ammo) = ammunition.get_mut(stack) {
+                    ammo.count += count;
+                } else if let Some(stack) = stacks.get_mut(stack) {
+                    stack.count += count;
+                }
+                entities
+                    .delete(pickup.item)
+                    .expect("Unable to delete merged stack");
+            } else {
+                positions.remove(pickup.item);
+                backpack
+                    .insert(
+                        pickup.item,
+                        InBackpack {
+                            owner: pickup.collected_by,
+                        },
+                    )
+                    .expect("Unable to insert backpack entry");
+            }
 
             if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!(
-                    "You picked up {}.",
-                    names.get(pickup.item).unwrap().name
-                ));
+                Logger::new(LogCategory::Item)
+                    .append("You picked up")
+                    .item(item_name)
+                    .append(".")
+                    .log(&mut gamelog);
             }
         }
 
@@ -51,6 +102,7 @@ impl<'a> System<'a> for ItemCollectionSystem {
     }
 }
 
+/// Equips wearable items, and queues what the others do, see `effects`.
 pub struct ItemUseSystem {}
 
 impl<'a> System<'a> for ItemUseSystem {
@@ -61,20 +113,14 @@ impl<'a> System<'a> for ItemUseSystem {
         WriteStorage<'a, WantsToUseItem>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, CombatStats>,
-        ReadStorage<'a, Consumable>,
-        ReadStorage<'a, ProvidesHealing>,
-        ReadStorage<'a, InflictsDamage>,
         ReadExpect<'a, Map>,
-        WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, AreaOfEffect>,
-        WriteStorage<'a, Confusion>,
         WriteStorage<'a, Equipped>,
         ReadStorage<'a, Equippable>,
         WriteStorage<'a, InBackpack>,
-        WriteExpect<'a, ParticleBuilder>,
-        ReadStorage<'a, Position>,
-        ReadStorage<'a, InflictsTeleportsSymetrically>,
-        WriteStorage<'a, TeleportsSymetrically>,
+        ReadStorage<'a, EquipmentBonus>,
+        WriteStorage<'a, Viewshed>,
+        WriteExpect<'a, EffectQueue>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -85,253 +131,109 @@ impl<'a> System<'a> for ItemUseSystem {
             mut wants_use_item,
             names,
             mut combat_stats,
-            consumables,
-            provide_healing,
-            inflicts_damage,
             map,
-            mut suffer_damage,
             aoe,
-            mut confused,
             mut equipped,
             equippable,
             mut backpack,
-            mut particle_builder,
-            positions,
-            inflicts_tp,
-            mut receive_tp,
+            bonuses,
+            mut viewsheds,
+            mut effects,
         ) = data;
 
         for (entity, want_use_item) in (&entities, &wants_use_item).join() {
-            let mut used_item = true;
-
-            let mut targets: Vec<Entity> = Vec::new();
-            match want_use_item.target {
-                None => {
-                    targets.push(*player_entity);
-                }
-                Some(target) => {
-                    let area_effect = aoe.get(want_use_item.item);
-                    match area_effect {
-                        None => {
-                            // single target in tile
-                            let idx = map.get_index_at(target.x, target.y);
-                            for mob in map.tile_content[idx].iter() {
-                                targets.push(*mob);
-                            }
-                        }
-                        Some(area_effect) => {
-                            let mut blast_tiles =
-                                rltk::field_of_view(target, area_effect.radius, &*map);
-                            blast_tiles.retain(|p| {
-                                p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
-                            });
-                            for tile_idx in blast_tiles.iter() {
-                                let idx = map.get_index_at(tile_idx.x, tile_idx.y);
-                                for mob in map.tile_content[idx].iter() {
-                                    targets.push(*mob);
-                                }
-                                particle_builder.request(
-                                    tile_idx.x,
-                                    tile_idx.y,
-                                    rltk::RGB::named(rltk::ORANGE),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('░'),
-                                    200.0,
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-
-            let item_equippable = equippable.get(want_use_item.item);
-            match item_equippable {
-                None => {}
-                Some(item_to_equip) => {
-                    let target_slot = item_to_equip.slot;
-                    let target = targets[0];
-
-                    // remove any item the target has in the item slot
-                    let mut to_unequip: Vec<Entity> = Vec::new();
-                    for (item_entity, already_equipped, name) in
-                        (&entities, &equipped, &names).join()
-                    {
-                        if already_equipped.owner == target && already_equipped.slot == target_slot
-                        {
-                            to_unequip.push(item_entity);
-                            if target == *player_entity {
-                                gamelog.entries.push(format!("You unequip {}.", name.name));
-                            }
-                        }
-                    }
-
-                    // unequip + put in backpack
-                    for item in to_unequip.iter() {
-                        equipped.remove(*item);
-                        backpack
-                            .insert(*item, InBackpack { owner: target })
-                            .expect("Unable to insert backpack component");
-                    }
-
-                    // remove from backpack and equip item
-                    equipped
-                        .insert(
-                            want_use_item.item,
-                            Equipped {
-                                owner: target,
-                                slot: target_slot,
-                            },
-                        )
-                        .expect("Unable to insert equippable");
-                    backpack.remove(want_use_item.item);
-                    if target == *player_entity {
-                        gamelog.entries.push(format!(
-                            "You equip {}.",
-                            names.get(want_use_item.item).unwrap().name
-                        ));
-                    }
-                }
-            }
-
-            let item_heals = provide_healing.get(want_use_item.item);
-            match item_heals {
-                None => {}
-                Some(healer) => {
-                    used_item = false;
-                    for target in targets.iter() {
-                        let stats = combat_stats.get_mut(*target);
-                        if let Some(stats) = stats {
-                            stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
-                            if entity == *player_entity {
-                                gamelog.entries.push(format!(
-                                    "You use {}, healing {} hp",
-                                    names.get(want_use_item.item).unwrap().name,
-                                    healer.heal_amount
-                                ));
-                            }
-                            used_item = true;
-
-                            let pos = positions.get(*target);
-                            if let Some(pos) = pos {
-                                particle_builder.request(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::GREEN),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('♥'),
-                                    200.0,
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-
-            let item_damages = inflicts_damage.get(want_use_item.item);
-            match item_damages {
-                None => {}
-                Some(damage) => {
-                    used_item = false;
-                    for mob in targets.iter() {
-                        SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
-                        if entity == *player_entity {
-                            let mob_name = names.get(*mob).unwrap();
-                            let item_name = names.get(want_use_item.item).unwrap();
-                            gamelog.entries.push(format!(
-                                "You use {} on {}, inflincting {} hp.",
-                                item_name.name, mob_name.name, damage.damage
-                            ));
-
-                            let pos = positions.get(*mob);
-
-                            if let Some(pos) = pos {
-                                particle_builder.request(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::RED),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('‼'),
-                                    200.0,
-                                );
-                            }
-                        }
-                    }
-                    used_item = true;
-                }
-            }
-
-            let item_teleports_victim = inflicts_tp.get(want_use_item.item);
-            match item_teleports_victim {
-                None => {}
-                Some(tp) => {
-                    used_item = false;
-                    for mob in targets.iter() {
-                        receive_tp
-                            .insert(*mob, TeleportsSymetrically { from: entity })
-                            .expect("failed to insert tp");
-                        if entity == *player_entity {
-                            let mob_name = names.get(*mob).unwrap();
-                            let item_name = names.get(want_use_item.item).unwrap();
-                            gamelog.entries.push(format!(
-                                "You use {} on {}, Teleporting them",
-                                item_name.name, mob_name.name
-                            ));
-                        }
-                    }
-                    used_item = true;
-                }
-            }
-
-            let mut confused_victims = Vec::new();
-            {
-                let causes_confusion = confused.get(want_use_item.item);
-                match causes_confusion {
-                    None => {}
-                    Some(confusion) => {
-                        used_item = false;
-                        for mob in targets.iter() {
-                            confused_victims.push((*mob, confusion.turns));
-                            if entity == *player_entity {
-                                let mob_name = names.get(*mob).unwrap();
-                                let item_name = names.get(want_use_item.item).unwrap();
-                                gamelog.entries.push(format!(
-                                    "You use {} on {}, confusing them",
-                                    item_name.name, mob_name.name
-                                ));
-                                let pos = positions.get(*mob);
-
-                                if let Some(pos) = pos {
-                                    particle_builder.request(
-                                        pos.x,
-                                        pos.y,
-                                        rltk::RGB::named(rltk::PURPLE),
-                                        rltk::RGB::named(rltk::BLACK),
-                                        rltk::to_cp437('?'),
-                                        200.0,
-                                    );
-                                }
-                            }
+            let targets = match want_use_item.target {
+                None => Targets::Single {
+                    target: *player_entity,
+                },
+                Some(target) => match aoe.get(want_use_item.item) {
+                    None => Targets::Tile {
+                        tile_idx: map.get_index_at(target.x, target.y),
+                    },
+                    Some(area_effect) => {
+                        let mut blast_tiles =
+                            rltk::field_of_view(target, area_effect.radius, &*map);
+                        blast_tiles.retain(|p| {
+                            p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
+                        });
+                        Targets::Tiles {
+                            tiles: blast_tiles
+                                .iter()
+                                .map(|p| map.get_index_at(p.x, p.y))
+                                .collect(),
                         }
-                        used_item = true;
                     }
+                },
+            };
+
+            let Some(item_to_equip) = equippable.get(want_use_item.item) else {
+                effects.push(
+                    Some(entity),
+                    EffectType::ItemUse {
+                        item: want_use_item.item,
+                    },
+                    targets,
+                );
+                continue;
+            };
+            let target_slot = item_to_equip.slot;
+            let target = match targets {
+                Targets::Single { target } => target,
+                _ => entity,
+            };
+
+            // make room in the slot, the first item worn there goes back to the backpack
+            let worn: Vec<Entity> = (&entities, &equipped)
+                .join()
+                .filter(|(_, e)| e.owner == target && e.slot == target_slot)
+                .map(|(item, _)| item)
+                .collect();
+            if worn.len() >= target_slot.capacity() {
+                let item = worn[0];
+                unequip(
+                    item,
+                    &mut equipped,
+                    &bonuses,
+                    &mut combat_stats,
+                    &mut viewsheds,
+                );
+                backpack
+                    .insert(item, InBackpack { owner: target })
+                    .expect("Unable to insert backpack component");
+                if target == *player_entity {
+                    Logger::new(LogCategory::Item)
+                        .append("You unequip")
+                        .item(&names.get(item).unwrap().name)
+                        .append(".")
+                        .log(&mut gamelog);
                 }
             }
-            for mob in confused_victims.iter() {
-                confused
-                    .insert(mob.0, Confusion { turns: mob.1 })
-                    .expect("Unable to insert confused status");
-            }
 
-            if used_item {
-                let consumable = consumables.get(want_use_item.item);
-                match consumable {
-                    None => {}
-                    Some(_) => {
-                        entities
-                            .delete(want_use_item.item)
-                            .expect("Delete consumable failed");
-                    }
-                }
+            // remove from backpack and equip item
+            equipped
+                .insert(
+                    want_use_item.item,
+                    Equipped {
+                        owner: target,
+                        slot: target_slot,
+                    },
+                )
+                .expect("Unable to insert equippable");
+            apply_equipment(
+                target,
+                want_use_item.item,
+                1,
+                &bonuses,
+                &mut combat_stats,
+                &mut viewsheds,
+            );
+            backpack.remove(want_use_item.item);
+            if target == *player_entity {
+                Logger::new(LogCategory::Item)
+                    .append("You equip")
+                    .item(&names.get(want_use_item.item).unwrap().name)
+                    .append(".")
+                    .log(&mut gamelog);
             }
         }
 
@@ -339,6 +241,8 @@ impl<'a> System<'a> for ItemUseSystem {
     }
 }
 
+/// Drops items where their owner stands. A stack loses a single item, which
+/// is spawned anew on the floor.
 pub struct ItemDropSystem {}
 
 impl<'a> System<'a> for ItemDropSystem {
@@ -350,6 +254,8 @@ impl<'a> System<'a> for ItemDropSystem {
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, Stackable>,
+        Read<'a, LazyUpdate>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -361,6 +267,8 @@ impl<'a> System<'a> for ItemDropSystem {
             names,
             mut positions,
             mut in_backpack,
+            mut stacks,
+            lazy,
         ) = data;
 
         for (entity, to_drop) in (&entities, &wants_drop).join() {
@@ -370,23 +278,38 @@ impl<'a> System<'a> for ItemDropSystem {
                 dropper_pos.x = dropped_pos.x;
                 dropper_pos.y = dropped_pos.y;
             }
-
-            positions
-                .insert(
-                    to_drop.item,
-                    Position {
-                        x: dropper_pos.x,
-                        y: dropper_pos.y,
-                    },
-                )
-                .expect("Unable to insert position to dropped item");
-            in_backpack.remove(to_drop.item);
+            let item_name = &names.get(to_drop.item).unwrap().name;
+
+            if let Some(stack) = stacks.get_mut(to_drop.item).filter(|s| s.count > 1) {
+                stack.count -= 1;
+                let (name, x, y) = (item_name.clone(), dropper_pos.x, dropper_pos.y);
+                lazy.exec_mut(move |world| {
+                    spawn_named_item(
+                        &RAWS.lock().unwrap(),
+                        world.create_entity(),
+                        &name,
+                        SpawnType::AtPosition { x, y },
+                    );
+                });
+            } else {
+                positions
+                    .insert(
+                        to_drop.item,
+                        Position {
+                            x: dropper_pos.x,
+                            y: dropper_pos.y,
+                        },
+                    )
+                    .expect("Unable to insert position to dropped item");
+                in_backpack.remove(to_drop.item);
+            }
 
             if entity == *player_entity {
-                gamelog.entries.push(format!(
-                    "You drop {}.",
-                    names.get(to_drop.item).unwrap().name
-                ));
+                Logger::new(LogCategory::Item)
+                    .append("You drop")
+                    .item(item_name)
+                    .append(".")
+                    .log(&mut gamelog);
             }
         }
         wants_drop.clear();
@@ -401,13 +324,30 @@ impl<'a> System<'a> for ItemRemoveSystem {
         WriteStorage<'a, WantsToRemoveItem>,
         WriteStorage<'a, Equipped>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, EquipmentBonus>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, Viewshed>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut wants_remove, mut equipped, mut backpack) = data;
+        let (
+            entities,
+            mut wants_remove,
+            mut equipped,
+            mut backpack,
+            bonuses,
+            mut combat_stats,
+            mut viewsheds,
+        ) = data;
 
         for (entity, to_remove) in (&entities, &wants_remove).join() {
-            equipped.remove(to_remove.item);
+            unequip(
+                to_remove.item,
+                &mut equipped,
+                &bonuses,
+                &mut combat_stats,
+                &mut viewsheds,
+            );
             backpack
                 .insert(to_remove.item, InBackpack { owner: entity })
                 .expect("Unable to insert removed item in backpack");
@@ -416,3 +356,47 @@ impl<'a> System<'a> for ItemRemoveSystem {
         wants_remove.clear();
     }
 }
+
+/// Takes `item` off whoever has it equipped, along with its bonuses.
+fn unequip(
+    item: Entity,
+    equipped: &mut WriteStorage<Equipped>,
+    bonuses: &ReadStorage<EquipmentBonus>,
+    combat_stats: &mut WriteStorage<CombatStats>,
+    viewsheds: &mut WriteStorage<Viewshed>,
+) {
+    if let Some(equipped_by) = equipped.remove(item) {
+        apply_equipment(
+            equipped_by.owner,
+            item,
+            -1,
+            bonuses,
+            combat_stats,
+            viewsheds,
+        );
+    }
+}
+
+/// Max HP is the one bonus kept in the wearer's stats, so it is added when the
+/// item goes on (`sign` 1) and taken away when it comes off (`sign` -1). The
+/// others are read from the equipment as needed, but a different vision range
+/// needs a new field of view.
+fn apply_equipment(
+    owner: Entity,
+    item: Entity,
+    sign: i32,
+    bonuses: &ReadStorage<EquipmentBonus>,
+    combat_stats: &mut WriteStorage<CombatStats>,
+    viewsheds: &mut WriteStorage<Viewshed>,
+) {
+    let Some(bonus) = bonuses.get(item) else {
+        return;
+    };
+    if let Some(stats) = combat_stats.get_mut(owner) {
+        stats.max_hp += sign * bonus.max_hp;
+        stats.hp = i32::min(stats.hp, stats.max_hp);
+    }
+    if let Some(viewshed) = viewsheds.get_mut(owner) {
+        viewshed.dirty = true;
+    }
+}
@@ -15,20 +15,48 @@ use components::*;
 mod map;
 use map::*;
 
+mod map_builders;
+
+mod dungeon;
+use dungeon::MasterDungeonMap;
+
 pub mod rect;
 
 mod visibility_system;
 use visibility_system::VisibilitySystem;
 
+mod flow_map_system;
+use flow_map_system::FlowMapSystem;
+
 mod monster_ai_system;
 use monster_ai_system::MonsterAI;
 
+mod initiative_system;
+use initiative_system::InitiativeSystem;
+
+mod hunger_system;
+use hunger_system::HungerSystem;
+
+mod status_effect_system;
+use status_effect_system::StatusEffectSystem;
+
+mod gamesystem;
+
 mod map_indexing_system;
 use map_indexing_system::MapIndexingSystem;
 
+mod trigger_system;
+use trigger_system::TriggerSystem;
+
 mod melee_combat_system;
 use melee_combat_system::MeleeCombatSystem;
 
+mod ranged_combat_system;
+use ranged_combat_system::RangedCombatSystem;
+
+mod spell_system;
+use spell_system::SpellSystem;
+
 mod damage_system;
 use damage_system::*;
 
@@ -45,29 +73,50 @@ mod random_table;
 
 mod particle_system;
 
+mod effects;
+
+mod raws;
+
+#[cfg(test)]
+mod headless;
+
 pub struct State {
     pub ecs: World,
 }
 
 impl State {
     fn run_systems(&mut self) {
+        self.ecs.write_resource::<gamelog::GameLog>().turn = self.ecs.fetch::<TurnCounter>().turns;
+
         let mut visibility = VisibilitySystem {};
         visibility.run_now(&self.ecs);
 
+        let mut flow_map = FlowMapSystem {};
+        flow_map.run_now(&self.ecs);
+
         let mut monster_ai = MonsterAI {};
         monster_ai.run_now(&self.ecs);
 
         let mut map_index = MapIndexingSystem {};
         map_index.run_now(&self.ecs);
 
+        let mut triggers = TriggerSystem {};
+        triggers.run_now(&self.ecs);
+
+        let mut hunger = HungerSystem {};
+        hunger.run_now(&self.ecs);
+
+        let mut status_effects = StatusEffectSystem {};
+        status_effects.run_now(&self.ecs);
+
         let mut melee_combat = MeleeCombatSystem {};
         melee_combat.run_now(&self.ecs);
 
-        let mut damage_system = DamageSystem {};
-        damage_system.run_now(&self.ecs);
+        let mut ranged_combat = RangedCombatSystem {};
+        ranged_combat.run_now(&self.ecs);
 
-        let mut tps = TeleportedSymSystem {};
-        tps.run_now(&self.ecs);
+        let mut spells = SpellSystem {};
+        spells.run_now(&self.ecs);
 
         let mut pickup = ItemCollectionSystem {};
         pickup.run_now(&self.ecs);
@@ -81,6 +130,11 @@ impl State {
         let mut item_remove = ItemRemoveSystem {};
         item_remove.run_now(&self.ecs);
 
+        effects::run_effects_queue(&mut self.ecs);
+
+        let mut damage_system = DamageSystem {};
+        damage_system.run_now(&self.ecs);
+
         let mut particles = particle_system::ParticleSpawnSystem {};
         particles.run_now(&self.ecs);
 
@@ -101,14 +155,27 @@ impl GameState for State {
 
         match new_run_state {
             RunState::MainMenu { .. } => {}
+            RunState::ShowLog { .. } => {}
+            RunState::ShowSaveSlots {
+                mode: gui::SlotMenuMode::Load,
+                ..
+            } => {}
             _ => {
                 draw_map(&self.ecs, ctx);
 
                 let positions = self.ecs.read_storage::<Position>();
                 let renderables = self.ecs.read_storage::<Renderable>();
+                let particles = self.ecs.read_storage::<ParticleLifetime>();
+                let hidden = self.ecs.read_storage::<Hidden>();
                 let map = self.ecs.fetch::<Map>();
 
-                let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+                // particles still waiting for their turn stay hidden
+                let mut data = (&positions, &renderables, particles.maybe(), !&hidden)
+                    .join()
+                    .map(|(pos, render, particle, _)| (pos, render, particle))
+                    .filter(|(_, _, particle)| particle.is_none_or(|p| p.delay_ms <= 0.0))
+                    .map(|(pos, render, _)| (pos, render))
+                    .collect::<Vec<_>>();
                 data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
                 for (pos, render) in data.iter() {
                     let idx = map.get_index_at(pos.x, pos.y);
@@ -124,21 +191,69 @@ impl GameState for State {
             RunState::PrePun => {
                 self.run_systems();
                 self.ecs.maintain();
-                new_run_state = RunState::AwaitingInput;
+                new_run_state = RunState::Ticking;
             }
             RunState::AwaitingInput => {
                 new_run_state = player_input(self, ctx);
             }
+            RunState::AutoWalking => {
+                let interrupted = ctx.key.is_some() || ctx.left_click;
+                new_run_state = player::auto_walk(&mut self.ecs, interrupted);
+            }
             RunState::PlayerTurn => {
+                let player_entity = *self.ecs.fetch::<Entity>();
+                let shooting = self
+                    .ecs
+                    .read_storage::<WantsToShoot>()
+                    .contains(player_entity);
+                let cost = if shooting {
+                    initiative_system::AIMED_TURN_COST
+                } else {
+                    initiative_system::TURN_COST
+                };
                 self.run_systems();
                 // for potions to actually be deleted
                 self.ecs.maintain();
-                new_run_state = RunState::MonsterTurn;
+                initiative_system::end_turn(
+                    player_entity,
+                    cost,
+                    &mut self.ecs.write_storage::<Energy>(),
+                    &mut self.ecs.write_storage::<MyTurn>(),
+                );
+                self.ecs.write_resource::<TurnCounter>().turns += 1;
+                new_run_state = RunState::Ticking;
             }
-            RunState::MonsterTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                new_run_state = RunState::AwaitingInput;
+            RunState::Ticking => {
+                // play out the monsters' turns until the player's comes
+                while new_run_state == RunState::Ticking {
+                    let mut initiative = InitiativeSystem {};
+                    initiative.run_now(&self.ecs);
+                    let player_entity = *self.ecs.fetch::<Entity>();
+                    if self.ecs.read_storage::<MyTurn>().contains(player_entity) {
+                        new_run_state = match *self.ecs.fetch::<AutoWalk>() {
+                            AutoWalk::Off => RunState::AwaitingInput,
+                            _ => RunState::AutoWalking,
+                        };
+                        break;
+                    }
+                    // nothing to play out until someone's turn comes
+                    let someone_acts = (
+                        &self.ecs.read_storage::<MyTurn>(),
+                        &self.ecs.read_storage::<Position>(),
+                    )
+                        .join()
+                        .next()
+                        .is_some();
+                    if !someone_acts {
+                        continue;
+                    }
+                    self.run_systems();
+                    initiative_system::pass_unclaimed_turns(&self.ecs);
+                    damage_system::delete_the_dead(&mut self.ecs);
+                    if *self.ecs.fetch::<RunState>() == RunState::GameOver {
+                        new_run_state = RunState::GameOver;
+                    }
+                }
             }
             RunState::ShowInventory => {
                 let result = gui::show_inventory(self, ctx);
@@ -195,16 +310,29 @@ impl GameState for State {
                     gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
-                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToUseItem {
-                                    item,
-                                    target: result.1,
-                                },
-                            )
-                            .expect("Unable to insert intent to use item");
+                        let player = *self.ecs.fetch::<Entity>();
+                        if self.ecs.read_storage::<RangedWeapon>().contains(item) {
+                            let mut intent = self.ecs.write_storage::<WantsToShoot>();
+                            intent
+                                .insert(
+                                    player,
+                                    WantsToShoot {
+                                        target: result.1.unwrap(),
+                                    },
+                                )
+                                .expect("Unable to insert intent to shoot");
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                            intent
+                                .insert(
+                                    player,
+                                    WantsToUseItem {
+                                        item,
+                                        target: result.1,
+                                    },
+                                )
+                                .expect("Unable to insert intent to use item");
+                        }
                         new_run_state = RunState::PlayerTurn;
                     }
                 }
@@ -218,10 +346,20 @@ impl GameState for State {
                         }
                     }
                     gui::MainMenuResult::Selected { selected } => match selected {
-                        gui::MainMenuSelection::NewGame => new_run_state = RunState::PrePun,
+                        gui::MainMenuSelection::NewGame => {
+                            self.new_game(false);
+                            new_run_state = RunState::PrePun;
+                        }
+                        gui::MainMenuSelection::NewPermadeathGame => {
+                            self.new_game(true);
+                            new_run_state = RunState::PrePun;
+                        }
                         gui::MainMenuSelection::LoadGame => {
-                            saveload_system::load_game(&mut self.ecs);
-                            new_run_state = RunState::AwaitingInput;
+                            saveload_system::refresh_save_slots(&self.ecs);
+                            new_run_state = RunState::ShowSaveSlots {
+                                mode: gui::SlotMenuMode::Load,
+                                selection: 0,
+                            };
                         }
                         gui::MainMenuSelection::Quit => {
                             ::std::process::exit(0);
@@ -229,14 +367,82 @@ impl GameState for State {
                     },
                 }
             }
-            RunState::SaveGame => {
-                saveload_system::save_game(&mut self.ecs);
-                new_run_state = RunState::MainMenu {
-                    menu_selection: gui::MainMenuSelection::LoadGame,
-                };
+            RunState::ShowSaveSlots { mode, selection } => {
+                let result = gui::save_slot_menu(self, ctx, mode, selection);
+                match result {
+                    gui::SlotMenuResult::NoSelection { selected } => {
+                        new_run_state = RunState::ShowSaveSlots {
+                            mode,
+                            selection: selected,
+                        };
+                    }
+                    gui::SlotMenuResult::Cancel => match mode {
+                        gui::SlotMenuMode::Save => new_run_state = RunState::AwaitingInput,
+                        gui::SlotMenuMode::Load => {
+                            new_run_state = RunState::MainMenu {
+                                menu_selection: gui::MainMenuSelection::LoadGame,
+                            };
+                        }
+                    },
+                    gui::SlotMenuResult::Delete { slot } => {
+                        if let Err(e) = saveload_system::delete_save(&self.ecs, slot) {
+                            self.ecs.insert(gui::MenuMessage {
+                                text: e.to_string(),
+                            });
+                        }
+                    }
+                    gui::SlotMenuResult::Selected { slot } => match mode {
+                        gui::SlotMenuMode::Save => {
+                            match saveload_system::save_game(&mut self.ecs, slot) {
+                                Ok(()) => {
+                                    new_run_state = RunState::MainMenu {
+                                        menu_selection: gui::MainMenuSelection::LoadGame,
+                                    };
+                                }
+                                Err(e) => {
+                                    self.ecs.insert(gui::MenuMessage {
+                                        text: e.to_string(),
+                                    });
+                                }
+                            }
+                        }
+                        gui::SlotMenuMode::Load => {
+                            match saveload_system::load_game(&mut self.ecs, slot) {
+                                Ok(()) => {
+                                    self.ecs.remove::<gui::MenuMessage>();
+                                    new_run_state = RunState::AwaitingInput;
+                                }
+                                Err(e) => {
+                                    // the world may be half loaded, start over from a new one
+                                    self.game_over_cleanup();
+                                    self.ecs.insert(gui::MenuMessage {
+                                        text: e.to_string(),
+                                    });
+                                }
+                            }
+                        }
+                    },
+                }
+            }
+            RunState::ShowLog { scroll, filter } => {
+                match gui::show_log(self, ctx, scroll, filter) {
+                    gui::LogViewResult::Close => new_run_state = RunState::AwaitingInput,
+                    gui::LogViewResult::NoResponse { scroll, filter } => {
+                        new_run_state = RunState::ShowLog { scroll, filter };
+                    }
+                }
+            }
+            RunState::ShowCharacter => {
+                if gui::show_character(self, ctx) == gui::ItemMenuResult::Cancel {
+                    new_run_state = RunState::AwaitingInput;
+                }
             }
             RunState::NextLevel => {
-                self.goto_next_level();
+                self.goto_level(1);
+                new_run_state = RunState::PrePun;
+            }
+            RunState::PreviousLevel => {
+                self.goto_level(-1);
                 new_run_state = RunState::PrePun;
             }
             RunState::ShowRemoveItem => {
@@ -258,7 +464,9 @@ impl GameState for State {
                 }
             }
             RunState::GameOver => {
-                let result = gui::game_over(ctx);
+                saveload_system::delete_permadeath_save(&self.ecs);
+                let seed = self.ecs.fetch::<GameSeed>().seed;
+                let result = gui::game_over(ctx, seed);
                 match result {
                     gui::GameOverResult::NoSelection => {}
                     gui::GameOverResult::QuitToMenu => {
@@ -298,17 +506,39 @@ fn main() -> rltk::BError {
         .build()?;
     context.with_post_scanlines(true);
 
+    let gs = init_state(seed_from_args());
+
+    rltk::main_loop(context, gs)
+}
+
+/// Builds the world, registers every component and generates the first level.
+/// Shared by the windowed game and the headless driver.
+fn init_state(seed: u64) -> State {
     let mut gs = State { ecs: World::new() };
 
     gs.ecs.insert(rltk::RandomNumberGenerator::new());
     gs.ecs.insert(particle_system::ParticleBuilder::new());
+    gs.ecs.insert(effects::EffectQueue::default());
+    gs.ecs.insert(flow_map_system::PlayerFlowMap::default());
 
     // Register components to ECS
     gs.ecs.register::<Position>();
+    gs.ecs.register::<OtherLevelPosition>();
+    gs.ecs.register::<Energy>();
+    gs.ecs.register::<MyTurn>();
+    gs.ecs.register::<HungerClock>();
+    gs.ecs.register::<Experience>();
+    gs.ecs.register::<Attributes>();
+    gs.ecs.register::<Skills>();
+    gs.ecs.register::<ProvidesFood>();
     gs.ecs.register::<Renderable>();
     gs.ecs.register::<Player>();
     gs.ecs.register::<Viewshed>();
     gs.ecs.register::<Monster>();
+    gs.ecs.register::<Brain>();
+    gs.ecs.register::<Faction>();
+    gs.ecs.register::<SpellBook>();
+    gs.ecs.register::<WantsToCast>();
     gs.ecs.register::<Name>();
     gs.ecs.register::<BlocksTile>();
     gs.ecs.register::<CombatStats>();
@@ -324,46 +554,67 @@ fn main() -> rltk::BError {
     gs.ecs.register::<Ranged>();
     gs.ecs.register::<InflictsDamage>();
     gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<Confusion>();
+    gs.ecs.register::<InflictsStatus>();
+    gs.ecs.register::<StatusEffects>();
     gs.ecs.register::<Equippable>();
     gs.ecs.register::<Equipped>();
-    gs.ecs.register::<MeleePowerBonus>();
-    gs.ecs.register::<DefenseBonus>();
+    gs.ecs.register::<MeleeWeapon>();
+    gs.ecs.register::<RangedWeapon>();
+    gs.ecs.register::<Ammunition>();
+    gs.ecs.register::<Stackable>();
+    gs.ecs.register::<WantsToShoot>();
+    gs.ecs.register::<EquipmentBonus>();
     gs.ecs.register::<WantsToRemoveItem>();
     gs.ecs.register::<ParticleLifetime>();
-    gs.ecs.register::<TeleportsSymetrically>();
     gs.ecs.register::<InflictsTeleportsSymetrically>();
+    gs.ecs.register::<TeleportsRandomly>();
+    gs.ecs.register::<Hidden>();
+    gs.ecs.register::<EntryTrigger>();
+    gs.ecs.register::<SingleActivation>();
+    gs.ecs.register::<EntityMoved>();
+    gs.ecs.register::<Door>();
+    gs.ecs.register::<BlocksVisibility>();
+    gs.ecs.register::<Key>();
 
     gs.ecs.register::<SimpleMarker<SerializeMe>>();
     gs.ecs.register::<SerializationHelper>();
 
     gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
 
-    let map = Map::new_map_rooms_and_corridors(1);
-
-    for room in map.rooms.iter().skip(1) {
-        spawner::spawn_room_content(&mut gs.ecs, room, 1)
-    }
-
-    let (player_x, player_y) = map.rooms[0].center();
-    gs.ecs.insert(map);
-    gs.ecs.insert(Point::new(player_x, player_y));
-
-    let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
+    raws::load_raws();
 
+    gs.ecs.insert(GameSeed { seed });
+    gs.ecs.insert(TurnCounter { turns: 0 });
+    gs.ecs.insert(GameMode {
+        permadeath: false,
+        slot: None,
+    });
+    gs.ecs.insert(saveload_system::SaveDirectory {
+        path: std::path::PathBuf::from("./saves"),
+    });
+    gs.ecs.insert(saveload_system::SaveSlots::default());
+    saveload_system::refresh_save_slots(&gs.ecs);
+    gs.ecs.insert(Map::default());
+    gs.ecs.insert(MasterDungeonMap::default());
+    gs.ecs.insert(Point::new(0, 0));
+    gs.ecs.insert(Aiming { x: 0, y: 0 });
+    gs.ecs.insert(AutoWalk::Off);
+
+    let player_entity = spawner::player(&mut gs.ecs, 0, 0);
     gs.ecs.insert(player_entity);
+
     gs.ecs.insert(RunState::MainMenu {
         menu_selection: gui::MainMenuSelection::NewGame,
     });
-    gs.ecs.insert(gamelog::GameLog {
-        entries: vec!["Welcome".to_string()],
-    });
-    gs.ecs.insert(Aiming {
-        x: player_x,
-        y: player_y,
-    });
+    let mut log = gamelog::GameLog::default();
+    gamelog::Logger::new(gamelog::LogCategory::System)
+        .append("Welcome")
+        .log(&mut log);
+    gs.ecs.insert(log);
 
-    rltk::main_loop(context, gs)
+    gs.generate_world_map(1);
+
+    gs
 }
 
 pub struct Aiming {
@@ -371,12 +622,45 @@ pub struct Aiming {
     y: i32,
 }
 
+/// Where the player is walking by themselves, a step per turn, while the game
+/// is in `RunState::AutoWalking`.
+#[derive(PartialEq, Copy, Clone)]
+pub enum AutoWalk {
+    Off,
+    /// Towards the nearest tile not seen yet.
+    Explore,
+    /// Along the shortest known way to a tile.
+    Travel {
+        x: i32,
+        y: i32,
+    },
+}
+
+/// Master seed of the current run, every level is generated from it.
+pub struct GameSeed {
+    pub seed: u64,
+}
+
+/// Turns played in the current run.
+pub struct TurnCounter {
+    pub turns: i32,
+}
+
+/// How the current run treats its saves. A permadeath save is consumed when
+/// it is loaded, and the run's slot is deleted when the player dies.
+pub struct GameMode {
+    pub permadeath: bool,
+    /// The slot the run was last saved to or loaded from.
+    pub slot: Option<usize>,
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
     AwaitingInput,
+    AutoWalking,
     PrePun,
     PlayerTurn,
-    MonsterTurn,
+    Ticking,
     ShowInventory,
     ShowDropItem,
     ShowTargeting {
@@ -386,95 +670,81 @@ pub enum RunState {
     MainMenu {
         menu_selection: gui::MainMenuSelection,
     },
-    SaveGame,
+    ShowSaveSlots {
+        mode: gui::SlotMenuMode,
+        selection: usize,
+    },
+    ShowLog {
+        scroll: usize,
+        filter: Option<gamelog::LogCategory>,
+    },
     NextLevel,
+    PreviousLevel,
     ShowRemoveItem,
+    ShowCharacter,
     GameOver,
 }
 
 impl State {
-    fn entities_to_remove_on_depth_change(&mut self) -> Vec<Entity> {
-        let entities = self.ecs.entities();
-        let player = self.ecs.read_storage::<Player>();
-        let backpack = self.ecs.read_storage::<InBackpack>();
-        let player_entity = self.ecs.fetch::<Entity>();
-        let equipped = self.ecs.read_storage::<Equipped>();
-
-        let mut to_delete: Vec<Entity> = Vec::new();
-
-        for entity in entities.join() {
-            let mut should_delete = true;
-            let p = player.get(entity);
-            if let Some(_p) = p {
-                should_delete = false;
-            }
-
-            let bp = backpack.get(entity);
-            if let Some(bp) = bp {
-                if bp.owner == *player_entity {
-                    should_delete = false;
-                }
-            }
-
-            let eq = equipped.get(entity);
-            if let Some(eq) = eq {
-                if eq.owner == *player_entity {
-                    should_delete = false;
-                }
-            }
-
-            if should_delete {
-                to_delete.push(entity);
-            }
-        }
-
-        to_delete
-    }
-
-    fn goto_next_level(&mut self) {
-        let to_delete = self.entities_to_remove_on_depth_change();
-        for target in to_delete {
-            self.ecs
-                .delete_entity(target)
-                .expect("Unable to delete entity");
-        }
-
-        // build new map
-        let world_map;
-        let curr_depth;
+    /// Leaves the current level for the one `offset` levels deeper. The level
+    /// left behind is stored with its entities, and a level already visited
+    /// comes back from the store instead of being generated again.
+    fn goto_level(&mut self, offset: i32) {
+        dungeon::freeze_level_entities(&mut self.ecs);
+
+        let new_depth;
+        let stored_map;
         {
-            let mut world_map_resource = self.ecs.write_resource::<Map>();
-            curr_depth = world_map_resource.depth;
-            *world_map_resource = Map::new_map_rooms_and_corridors(curr_depth + 1);
-            world_map = world_map_resource.clone();
+            let map = self.ecs.fetch::<Map>();
+            let mut dungeon = self.ecs.write_resource::<MasterDungeonMap>();
+            dungeon.store_map(&map);
+            new_depth = map.depth + offset;
+            stored_map = dungeon.take_map(new_depth);
         }
 
-        for room in world_map.rooms.iter().skip(1) {
-            spawner::spawn_room_content(&mut self.ecs, room, curr_depth + 1);
+        match stored_map {
+            None => self.generate_world_map(new_depth),
+            Some(map) => {
+                // arrive by the stairs at the other end
+                let arrival = if offset > 0 {
+                    TileType::UpStairs
+                } else {
+                    TileType::DownStairs
+                };
+                let idx = map
+                    .tiles
+                    .iter()
+                    .position(|tile| *tile == arrival)
+                    .expect("Stored level has no stairs");
+                let (x, y) = map.get_xy_from_idx(idx);
+                *self.ecs.write_resource::<Map>() = map;
+                dungeon::thaw_level_entities(&mut self.ecs);
+                self.place_player(x, y);
+                // doors block the very first sight of the level
+                MapIndexingSystem {}.run_now(&self.ecs);
+            }
         }
 
-        let (new_room_center_x, new_room_center_y) = world_map.rooms[0].center();
-        let mut player_position = self.ecs.write_resource::<Point>();
-        *player_position = Point::new(new_room_center_x, new_room_center_y);
-
-        let mut position_components = self.ecs.write_storage::<Position>();
-        let player_entity = self.ecs.fetch::<Entity>();
-        let player_pos_comp = position_components.get_mut(*player_entity);
-        if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = new_room_center_x;
-            player_pos_comp.y = new_room_center_y;
-        }
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        let message = if offset > 0 {
+            "You descend to the next level"
+        } else {
+            "You climb back to the previous level"
+        };
+        gamelog::Logger::new(gamelog::LogCategory::System)
+            .append(message)
+            .log(&mut gamelog);
+    }
 
-        let mut viewsheds = self.ecs.write_storage::<Viewshed>();
-        let vs = viewsheds.get_mut(*player_entity);
-        if let Some(vs) = vs {
-            vs.dirty = true;
+    /// Starts playing from the main menu. The world in memory is kept unless it
+    /// has been saved, so that a save can't be played on from both copies.
+    fn new_game(&mut self, permadeath: bool) {
+        self.ecs.remove::<gui::MenuMessage>();
+        if self.ecs.fetch::<GameMode>().slot.is_some() {
+            self.game_over_cleanup();
         }
-
-        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
-        gamelog
-            .entries
-            .push("You descend to the next level".to_string());
+        let mut game_mode = self.ecs.write_resource::<GameMode>();
+        game_mode.permadeath = permadeath;
     }
 
     fn game_over_cleanup(&mut self) {
@@ -487,41 +757,101 @@ impl State {
             self.ecs.delete_entity(*del).expect("Deletion failed");
         }
 
-        // refacto with go to new depth / loading game
-        // difference is with spawn a new player here
-        // see comment below
-        let world_map;
+        // a new run gets a new seed
+        {
+            let mut game_seed = self.ecs.write_resource::<GameSeed>();
+            game_seed.seed = rltk::RandomNumberGenerator::new().next_u64();
+            let mut turn_counter = self.ecs.write_resource::<TurnCounter>();
+            turn_counter.turns = 0;
+            let mut game_mode = self.ecs.write_resource::<GameMode>();
+            game_mode.slot = None;
+            let mut dungeon = self.ecs.write_resource::<MasterDungeonMap>();
+            *dungeon = MasterDungeonMap::default();
+            *self.ecs.write_resource::<AutoWalk>() = AutoWalk::Off;
+        }
+
+        let player_entity = spawner::player(&mut self.ecs, 0, 0);
+        {
+            let mut player_entity_writer = self.ecs.write_resource::<Entity>();
+            *player_entity_writer = player_entity;
+        }
+
+        self.generate_world_map(1);
+    }
+
+    /// Builds the map for `new_depth`, fills it and moves the player to its start.
+    /// The RNG is reseeded from the run seed first, so a given seed always
+    /// produces the same level at a given depth.
+    fn generate_world_map(&mut self, new_depth: i32) {
+        let seed = self.ecs.fetch::<GameSeed>().seed;
+        let mut builder;
         {
+            let mut rng = self.ecs.write_resource::<rltk::RandomNumberGenerator>();
+            *rng = rltk::RandomNumberGenerator::seeded(level_seed(seed, new_depth));
+            builder = map_builders::random_builder(new_depth, &mut rng);
+            builder.build_map(&mut rng);
             let mut world_map_resource = self.ecs.write_resource::<Map>();
-            *world_map_resource = Map::new_map_rooms_and_corridors(1);
-            world_map = world_map_resource.clone();
+            *world_map_resource = builder.get_map();
         }
 
-        for room in world_map.rooms.iter().skip(1) {
-            spawner::spawn_room_content(&mut self.ecs, room, 1);
+        for region in builder.get_spawn_regions().iter() {
+            spawner::spawn_region(&mut self.ecs, region, new_depth);
         }
 
-        let (new_room_center_x, new_room_center_y) = world_map.rooms[0].center();
-        // this line is different
-        let player_entity = spawner::player(&mut self.ecs, new_room_center_x, new_room_center_y);
-        let mut player_position = self.ecs.write_resource::<Point>();
-        *player_position = Point::new(new_room_center_x, new_room_center_y);
-        let mut position_components = self.ecs.write_storage::<Position>();
+        // the way back up, there is nothing above the first level
+        let start = builder.get_starting_position();
+        let start_idx = self.ecs.fetch::<Map>().get_index_at(start.x, start.y);
+        spawner::spawn_doors(&mut self.ecs, &builder.get_door_positions(), start_idx);
+        if new_depth > 1 {
+            let mut map = self.ecs.write_resource::<Map>();
+            let idx = map.get_index_at(start.x, start.y);
+            map.tiles[idx] = TileType::UpStairs;
+        }
+        self.place_player(start.x, start.y);
+        // doors block the very first sight of the level
+        MapIndexingSystem {}.run_now(&self.ecs);
+    }
 
-        // these 2 lines are different
-        let mut player_entity_writer = self.ecs.write_resource::<Entity>();
-        *player_entity_writer = player_entity;
+    /// Moves the player, and what follows them around, to a spot of the current map.
+    fn place_player(&mut self, player_x: i32, player_y: i32) {
+        let mut player_position = self.ecs.write_resource::<Point>();
+        *player_position = Point::new(player_x, player_y);
 
-        let player_pos_comp = position_components.get_mut(player_entity);
+        let mut position_components = self.ecs.write_storage::<Position>();
+        let player_entity = self.ecs.fetch::<Entity>();
+        let player_pos_comp = position_components.get_mut(*player_entity);
         if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = new_room_center_x;
-            player_pos_comp.y = new_room_center_y;
+            player_pos_comp.x = player_x;
+            player_pos_comp.y = player_y;
         }
 
+        let mut aiming = self.ecs.write_resource::<Aiming>();
+        aiming.x = player_x;
+        aiming.y = player_y;
+
         let mut viewsheds = self.ecs.write_storage::<Viewshed>();
-        let vs = viewsheds.get_mut(player_entity);
+        let vs = viewsheds.get_mut(*player_entity);
         if let Some(vs) = vs {
             vs.dirty = true;
         }
     }
 }
+
+/// Derives the seed of one dungeon level from the run seed.
+fn level_seed(seed: u64, depth: i32) -> u64 {
+    seed ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Reads `--seed <number>` from the command line, or picks a random seed.
+fn seed_from_args() -> u64 {
+    let args: Vec<String> = env::args().collect();
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--seed" {
+            match args.get(i + 1).map(|s| s.parse::<u64>()) {
+                Some(Ok(seed)) => return seed,
+                _ => eprintln!("--seed expects an unsigned integer, using a random seed"),
+            }
+        }
+    }
+    rltk::RandomNumberGenerator::new().next_u64()
+}
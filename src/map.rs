@@ -1,18 +1,23 @@
+use super::components::{Door, EntryTrigger, Hidden, Position, Renderable};
 use super::rect::Rect;
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB};
+use rltk::{Algorithm2D, BaseMap, Point, Rltk, RGB};
 use serde::{Deserialize, Serialize};
-use specs::{Entity, World};
-use std::{cmp::{max, min}, collections::HashSet};
+use specs::prelude::*;
+use std::collections::HashSet;
 
 pub const MAPWIDTH: usize = 80;
 pub const MAPHEIGHT: usize = 43;
 pub const MAPCOUNT: usize = MAPHEIGHT * MAPWIDTH;
 
+/// Extra pathing cost of stepping on a known trap.
+pub const KNOWN_TRAP_COST: f32 = 20.0;
+
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     DownStairs,
+    UpStairs,
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -30,6 +35,17 @@ pub struct Map {
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+
+    /// Tiles with a trap that isn't hidden, filled by `MapIndexingSystem`.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub known_traps: HashSet<usize>,
+
+    /// Tiles that can't be seen through although they aren't walls, such as
+    /// closed doors. Filled by `MapIndexingSystem`.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked: HashSet<usize>,
 }
 
 impl Algorithm2D for Map {
@@ -40,12 +56,12 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, index: usize) -> bool {
-        self.tiles[index as usize] == TileType::Wall
+        self.tiles[index as usize] == TileType::Wall || self.view_blocked.contains(&index)
     }
 
     // where can I go from this tile
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
+        let mut exits: rltk::SmallVec<[(usize, f32); 10]> = rltk::SmallVec::new();
         let (x, y) = self.get_xy_from_idx(idx);
         let w = self.width as usize;
 
@@ -79,6 +95,13 @@ impl BaseMap for Map {
             exits.push(((idx + w) + 1, 1.45))
         };
 
+        // known traps are walked around when there is another way
+        for exit in exits.iter_mut() {
+            if self.known_traps.contains(&exit.0) {
+                exit.1 += KNOWN_TRAP_COST;
+            }
+        }
+
         exits
     }
 
@@ -113,8 +136,9 @@ impl Map {
         }
     }
 
-    pub fn new_map_rooms_and_corridors(new_depth: i32) -> Map {
-        let mut map = Map {
+    /// Creates a map of the given depth that is solid wall everywhere.
+    pub fn new(new_depth: i32) -> Map {
+        Map {
             tiles: vec![TileType::Wall; MAPCOUNT],
             rooms: Vec::new(),
             width: MAPWIDTH as i32,
@@ -125,78 +149,8 @@ impl Map {
             tile_content: vec![Vec::new(); MAPCOUNT],
             depth: new_depth,
             bloodstains: HashSet::new(),
-        };
-
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        let mut rng = RandomNumberGenerator::new();
-
-        for _ in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, map.width - w - 1) - 1;
-            let y = rng.roll_dice(1, map.height - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-            let mut ok = true;
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
-                    ok = false
-                }
-            }
-            if ok {
-                map.apply_room_to_map(&new_room);
-
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                    if rng.range(0, 2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-
-                map.rooms.push(new_room);
-            }
-        }
-
-        // Set stair position to center of last room.
-        let stairs_position = map.rooms[map.rooms.len() - 1].center();
-        let stairs_idx = map.get_index_at(stairs_position.0, stairs_position.1);
-        map.tiles[stairs_idx] = TileType::DownStairs;
-
-        map
-    }
-
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1..=room.y2 {
-            for x in room.x1 + 1..=room.x2 {
-                let idx = self.get_index_at(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
-
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        // ensure we go from lower x to higher x
-        for x in min(x1, x2)..=max(x1, x2) {
-            let idx = self.get_index_at(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
-        }
-    }
-
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
-        for y in min(y1, y2)..=max(y1, y2) {
-            let idx = self.get_index_at(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
+            known_traps: HashSet::new(),
+            view_blocked: HashSet::new(),
         }
     }
 
@@ -234,6 +188,10 @@ pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
                     glyph = rltk::to_cp437('>');
                     fg = RGB::from_f32(0.0, 1.0, 1.0);
                 }
+                TileType::UpStairs => {
+                    glyph = rltk::to_cp437('<');
+                    fg = RGB::from_f32(0.0, 1.0, 1.0);
+                }
             }
             if map.bloodstains.contains(&idx) {
                 bg = RGB::from_f32(0.75, 0., 0.)
@@ -251,6 +209,28 @@ pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
             y += 1;
         }
     }
+
+    // doors, and traps once found, are remembered like the walls
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let doors = ecs.read_storage::<Door>();
+    let hidden = ecs.read_storage::<Hidden>();
+    for (entity, pos, render, _hidden) in (&entities, &positions, &renderables, !&hidden).join() {
+        if !triggers.contains(entity) && !doors.contains(entity) {
+            continue;
+        }
+        let idx = map.get_index_at(pos.x, pos.y);
+        if !map.revealed_tiles[idx] {
+            continue;
+        }
+        let mut fg = render.fg;
+        if !map.visible_tiles[idx] {
+            fg = fg.to_greyscale();
+        }
+        ctx.set(pos.x, pos.y, fg, render.bg, render.glyph);
+    }
 }
 
 fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
@@ -0,0 +1,111 @@
+use super::{
+    common::{generate_chunk_regions, remove_unreachable_areas_returning_most_distant},
+    MapBuilder,
+};
+use crate::{
+    components::Position,
+    map::{Map, TileType},
+};
+use rltk::RandomNumberGenerator;
+
+/// Natural looking caves: random noise smoothed by a few automaton passes.
+pub struct CellularAutomataBuilder {
+    map: Map,
+    starting_position: Position,
+    spawn_regions: Vec<Vec<usize>>,
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        const ITERATIONS: i32 = 15;
+
+        // 55% floor to start with, borders stay walls
+        for y in 1..self.map.height - 1 {
+            for x in 1..self.map.width - 1 {
+                let idx = self.map.get_index_at(x, y);
+                if rng.roll_dice(1, 100) > 55 {
+                    self.map.tiles[idx] = TileType::Floor;
+                } else {
+                    self.map.tiles[idx] = TileType::Wall;
+                }
+            }
+        }
+
+        for _ in 0..ITERATIONS {
+            let mut new_tiles = self.map.tiles.clone();
+
+            for y in 1..self.map.height - 1 {
+                for x in 1..self.map.width - 1 {
+                    let idx = self.map.get_index_at(x, y);
+                    let neighbors = self.count_wall_neighbors(x, y);
+
+                    if neighbors > 4 || neighbors == 0 {
+                        new_tiles[idx] = TileType::Wall;
+                    } else {
+                        new_tiles[idx] = TileType::Floor;
+                    }
+                }
+            }
+
+            self.map.tiles = new_tiles;
+        }
+
+        // start on the floor tile closest to the middle of the map
+        self.starting_position = Position {
+            x: self.map.width / 2,
+            y: self.map.height / 2,
+        };
+        let mut start_idx = self
+            .map
+            .get_index_at(self.starting_position.x, self.starting_position.y);
+        while self.map.tiles[start_idx] != TileType::Floor {
+            self.starting_position.x -= 1;
+            start_idx = self
+                .map
+                .get_index_at(self.starting_position.x, self.starting_position.y);
+        }
+
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        self.spawn_regions = generate_chunk_regions(&self.map, start_idx);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        self.spawn_regions.clone()
+    }
+}
+
+impl CellularAutomataBuilder {
+    pub fn new(new_depth: i32) -> CellularAutomataBuilder {
+        CellularAutomataBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            spawn_regions: Vec::new(),
+        }
+    }
+
+    fn count_wall_neighbors(&self, x: i32, y: i32) -> i32 {
+        let mut neighbors = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let idx = self.map.get_index_at(x + dx, y + dy);
+                if self.map.tiles[idx] == TileType::Wall {
+                    neighbors += 1;
+                }
+            }
+        }
+        neighbors
+    }
+}
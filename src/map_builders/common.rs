@@ -0,0 +1,129 @@
+use crate::{
+    map::{Map, TileType},
+    rect::Rect,
+};
+use std::cmp::{max, min};
+
+pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.get_index_at(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+    // ensure we go from lower x to higher x
+    for x in min(x1, x2)..=max(x1, x2) {
+        let idx = map.get_index_at(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_vertical_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2)..=max(y1, y2) {
+        let idx = map.get_index_at(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+/// Walls off every floor tile the start can't reach and returns the index
+/// of the reachable tile furthest from it, which is where the stairs go.
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.populate_blocked();
+    let dijkstra_map = rltk::DijkstraMap::new(
+        map.width as usize,
+        map.height as usize,
+        &[start_idx],
+        &*map,
+        (map.width * map.height) as f32,
+    );
+
+    let mut exit_tile = (start_idx, 0.0f32);
+    for (idx, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor {
+            let distance = dijkstra_map.map[idx];
+            if distance == f32::MAX {
+                *tile = TileType::Wall;
+            } else if distance > exit_tile.1 {
+                exit_tile = (idx, distance);
+            }
+        }
+    }
+
+    exit_tile.0
+}
+
+/// Splits the floor of a map without rooms into square chunks to spawn in.
+/// The chunk holding the start is left empty.
+pub fn generate_chunk_regions(map: &Map, start_idx: usize) -> Vec<Vec<usize>> {
+    const CHUNK_SIZE: i32 = 16;
+
+    let chunks_wide = (map.width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let chunks_high = (map.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let mut regions: Vec<Vec<usize>> = vec![Vec::new(); (chunks_wide * chunks_high) as usize];
+
+    let (start_x, start_y) = map.get_xy_from_idx(start_idx);
+    let start_chunk = (start_y / CHUNK_SIZE) * chunks_wide + start_x / CHUNK_SIZE;
+
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        if *tile == TileType::Floor {
+            let (x, y) = map.get_xy_from_idx(idx);
+            let chunk = (y / CHUNK_SIZE) * chunks_wide + x / CHUNK_SIZE;
+            if chunk != start_chunk {
+                regions[chunk as usize].push(idx);
+            }
+        }
+    }
+
+    regions.retain(|region| !region.is_empty());
+    regions
+}
+
+/// Floor tiles inside every room but the first one, where the player starts.
+pub fn rooms_to_regions(map: &Map) -> Vec<Vec<usize>> {
+    let mut regions = Vec::new();
+    for room in map.rooms.iter().skip(1) {
+        let mut region = Vec::new();
+        for y in room.y1 + 1..=room.y2 {
+            for x in room.x1 + 1..=room.x2 {
+                region.push(map.get_index_at(x, y));
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+/// Where corridors cross the walls of the rooms: a floor tile in a room's
+/// wall, with wall on either side of it along that wall.
+pub fn door_positions(map: &Map) -> Vec<usize> {
+    let is_wall = |x: i32, y: i32| map.tiles[map.get_index_at(x, y)] == TileType::Wall;
+    let inside = |x: i32, y: i32| x >= 1 && x <= map.width - 2 && y >= 1 && y <= map.height - 2;
+
+    let mut doors = Vec::new();
+    for room in map.rooms.iter() {
+        for x in room.x1 + 1..=room.x2 {
+            for y in [room.y1, room.y2 + 1] {
+                if inside(x, y) && !is_wall(x, y) && is_wall(x - 1, y) && is_wall(x + 1, y) {
+                    doors.push(map.get_index_at(x, y));
+                }
+            }
+        }
+        for y in room.y1 + 1..=room.y2 {
+            for x in [room.x1, room.x2 + 1] {
+                if inside(x, y) && !is_wall(x, y) && is_wall(x, y - 1) && is_wall(x, y + 1) {
+                    doors.push(map.get_index_at(x, y));
+                }
+            }
+        }
+    }
+    doors.sort_unstable();
+    doors.dedup();
+    doors
+}
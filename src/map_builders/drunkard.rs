@@ -0,0 +1,125 @@
+use super::{
+    common::{generate_chunk_regions, remove_unreachable_areas_returning_most_distant},
+    MapBuilder,
+};
+use crate::{
+    components::Position,
+    map::{Map, TileType},
+};
+use rltk::RandomNumberGenerator;
+
+/// Diggers stumble around randomly until enough of the map is floor.
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    starting_position: Position,
+    spawn_regions: Vec<Vec<usize>>,
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        const DRUNKEN_LIFETIME: i32 = 400;
+        const FLOOR_PERCENT: f32 = 0.5;
+
+        self.starting_position = Position {
+            x: self.map.width / 2,
+            y: self.map.height / 2,
+        };
+        let start_idx = self
+            .map
+            .get_index_at(self.starting_position.x, self.starting_position.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        let total_tiles = self.map.width * self.map.height;
+        let desired_floor_tiles = (FLOOR_PERCENT * total_tiles as f32) as usize;
+        let mut floor_tile_count = self.count_floor_tiles();
+        let mut digger_count = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            // the first digger starts from the middle, the others from a dug tile
+            let (mut drunk_x, mut drunk_y) = (self.starting_position.x, self.starting_position.y);
+            if digger_count > 0 {
+                let floor_tiles: Vec<usize> = self
+                    .map
+                    .tiles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile == TileType::Floor)
+                    .map(|(idx, _)| idx)
+                    .collect();
+                let idx = floor_tiles[(rng.roll_dice(1, floor_tiles.len() as i32) - 1) as usize];
+                let (x, y) = self.map.get_xy_from_idx(idx);
+                drunk_x = x;
+                drunk_y = y;
+            }
+
+            let mut drunk_life = DRUNKEN_LIFETIME;
+            while drunk_life > 0 {
+                let drunk_idx = self.map.get_index_at(drunk_x, drunk_y);
+                self.map.tiles[drunk_idx] = TileType::Floor;
+
+                match rng.roll_dice(1, 4) {
+                    1 => {
+                        if drunk_x > 2 {
+                            drunk_x -= 1;
+                        }
+                    }
+                    2 => {
+                        if drunk_x < self.map.width - 2 {
+                            drunk_x += 1;
+                        }
+                    }
+                    3 => {
+                        if drunk_y > 2 {
+                            drunk_y -= 1;
+                        }
+                    }
+                    _ => {
+                        if drunk_y < self.map.height - 2 {
+                            drunk_y += 1;
+                        }
+                    }
+                }
+
+                drunk_life -= 1;
+            }
+
+            digger_count += 1;
+            floor_tile_count = self.count_floor_tiles();
+        }
+
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        self.spawn_regions = generate_chunk_regions(&self.map, start_idx);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        self.spawn_regions.clone()
+    }
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(new_depth: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            spawn_regions: Vec::new(),
+        }
+    }
+
+    fn count_floor_tiles(&self) -> usize {
+        self.map
+            .tiles
+            .iter()
+            .filter(|tile| **tile == TileType::Floor)
+            .count()
+    }
+}
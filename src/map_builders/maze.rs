@@ -0,0 +1,89 @@
+use super::{
+    common::{generate_chunk_regions, remove_unreachable_areas_returning_most_distant},
+    MapBuilder,
+};
+use crate::{
+    components::Position,
+    map::{Map, TileType},
+};
+use rltk::RandomNumberGenerator;
+
+/// A perfect maze carved with a recursive backtracker. Every maze cell is the
+/// floor tile at (2x + 1, 2y + 1), the tiles in between are the walls.
+pub struct MazeBuilder {
+    map: Map,
+    starting_position: Position,
+    spawn_regions: Vec<Vec<usize>>,
+}
+
+impl MapBuilder for MazeBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let cells_wide = (self.map.width - 1) / 2;
+        let cells_high = (self.map.height - 1) / 2;
+        let mut visited = vec![false; (cells_wide * cells_high) as usize];
+        let mut stack: Vec<(i32, i32)> = vec![(0, 0)];
+        visited[0] = true;
+        self.carve_cell(0, 0);
+
+        while let Some(&(cx, cy)) = stack.last() {
+            let mut neighbors: Vec<(i32, i32)> = Vec::new();
+            for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)].iter() {
+                let (nx, ny) = (cx + dx, cy + dy);
+                if nx >= 0
+                    && nx < cells_wide
+                    && ny >= 0
+                    && ny < cells_high
+                    && !visited[(ny * cells_wide + nx) as usize]
+                {
+                    neighbors.push((nx, ny));
+                }
+            }
+
+            if neighbors.is_empty() {
+                stack.pop();
+            } else {
+                let (nx, ny) = neighbors[(rng.roll_dice(1, neighbors.len() as i32) - 1) as usize];
+                visited[(ny * cells_wide + nx) as usize] = true;
+                // knock down the wall between both cells
+                let wall_idx = self.map.get_index_at(cx + nx + 1, cy + ny + 1);
+                self.map.tiles[wall_idx] = TileType::Floor;
+                self.carve_cell(nx, ny);
+                stack.push((nx, ny));
+            }
+        }
+
+        self.starting_position = Position { x: 1, y: 1 };
+        let start_idx = self.map.get_index_at(1, 1);
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        self.spawn_regions = generate_chunk_regions(&self.map, start_idx);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        self.spawn_regions.clone()
+    }
+}
+
+impl MazeBuilder {
+    pub fn new(new_depth: i32) -> MazeBuilder {
+        MazeBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            spawn_regions: Vec::new(),
+        }
+    }
+
+    fn carve_cell(&mut self, cell_x: i32, cell_y: i32) {
+        let idx = self.map.get_index_at(cell_x * 2 + 1, cell_y * 2 + 1);
+        self.map.tiles[idx] = TileType::Floor;
+    }
+}
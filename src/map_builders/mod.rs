@@ -0,0 +1,119 @@
+use super::{components::Position, map::Map, random_table::RandomTable};
+use rltk::RandomNumberGenerator;
+
+mod bsp_dungeon;
+mod cellular_automata;
+mod common;
+mod drunkard;
+mod maze;
+mod simple_map;
+
+use bsp_dungeon::BspDungeonBuilder;
+use cellular_automata::CellularAutomataBuilder;
+use drunkard::DrunkardsWalkBuilder;
+use maze::MazeBuilder;
+use simple_map::SimpleMapBuilder;
+
+pub trait MapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+    fn get_map(&self) -> Map;
+    fn get_starting_position(&self) -> Position;
+    /// Groups of floor tile indexes, each one is filled like a room by the spawner.
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>>;
+    /// Where doors go. Maps without rooms have none.
+    fn get_door_positions(&self) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+/// Picks the builder for a level. The first level is always rooms and corridors,
+/// deeper levels roll on a weighted table.
+pub fn random_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    if new_depth == 1 {
+        return Box::new(SimpleMapBuilder::new(new_depth));
+    }
+
+    let builder_table = RandomTable::new()
+        .add("Rooms and corridors", 4)
+        .add("BSP dungeon", 4)
+        .add("Cellular automata", 2 + new_depth)
+        .add("Drunkard's walk", 2 + new_depth)
+        .add("Maze", new_depth - 2);
+
+    builder_named(&builder_table.roll(rng), new_depth)
+}
+
+/// The builder `random_builder` rolled, by name. Rooms and corridors for an
+/// unknown name.
+pub fn builder_named(name: &str, new_depth: i32) -> Box<dyn MapBuilder> {
+    match name {
+        "BSP dungeon" => Box::new(BspDungeonBuilder::new(new_depth)),
+        "Cellular automata" => Box::new(CellularAutomataBuilder::new(new_depth)),
+        "Drunkard's walk" => Box::new(DrunkardsWalkBuilder::new(new_depth)),
+        "Maze" => Box::new(MazeBuilder::new(new_depth)),
+        _ => Box::new(SimpleMapBuilder::new(new_depth)),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::builder_named;
+    use crate::map::TileType;
+
+    #[test]
+    fn every_builder_makes_connected_levels_with_room_to_spawn() {
+        let builders = [
+            "Rooms and corridors",
+            "BSP dungeon",
+            "Cellular automata",
+            "Drunkard's walk",
+            "Maze",
+        ];
+        for name in builders {
+            for seed in 1..=5 {
+                let mut rng = rltk::RandomNumberGenerator::seeded(seed);
+                let mut builder = builder_named(name, 3);
+                builder.build_map(&mut rng);
+                let mut map = builder.get_map();
+                map.populate_blocked();
+                let start = builder.get_starting_position();
+                let start_idx = map.get_index_at(start.x, start.y);
+                let dijkstra_map = rltk::DijkstraMap::new(
+                    map.width as usize,
+                    map.height as usize,
+                    &[start_idx],
+                    &map,
+                    (map.width * map.height) as f32,
+                );
+                let reachable = |idx: usize| idx == start_idx || dijkstra_map.map[idx] < f32::MAX;
+                let walkable = |idx: usize| map.tiles[idx] != TileType::Wall;
+
+                assert!(
+                    (0..map.tiles.len())
+                        .filter(|idx| walkable(*idx))
+                        .all(reachable),
+                    "{} (seed {}) has floor cut off from the start",
+                    name,
+                    seed
+                );
+                let stairs = map.tiles.iter().position(|t| *t == TileType::DownStairs);
+                assert!(
+                    stairs.is_some_and(reachable),
+                    "{} (seed {}) has no way down",
+                    name,
+                    seed
+                );
+                let regions = builder.get_spawn_regions();
+                assert!(
+                    !regions.is_empty()
+                        && regions
+                            .iter()
+                            .all(|region| !region.is_empty()
+                                && region.iter().all(|idx| walkable(*idx))),
+                    "{} (seed {}) has nowhere to spawn",
+                    name,
+                    seed
+                );
+            }
+        }
+    }
+}
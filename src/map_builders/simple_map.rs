@@ -0,0 +1,94 @@
+use super::{
+    common::{
+        apply_horizontal_tunnel, apply_room_to_map, apply_vertical_tunnel, door_positions,
+        rooms_to_regions,
+    },
+    MapBuilder,
+};
+use crate::{
+    components::Position,
+    map::{Map, TileType},
+    rect::Rect,
+};
+use rltk::RandomNumberGenerator;
+
+/// The original generator: random rooms joined by L-shaped corridors.
+pub struct SimpleMapBuilder {
+    map: Map,
+    starting_position: Position,
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        const MAX_ROOMS: i32 = 30;
+        const MIN_SIZE: i32 = 6;
+        const MAX_SIZE: i32 = 10;
+
+        for _ in 0..MAX_ROOMS {
+            let w = rng.range(MIN_SIZE, MAX_SIZE);
+            let h = rng.range(MIN_SIZE, MAX_SIZE);
+            let x = rng.roll_dice(1, self.map.width - w - 1) - 1;
+            let y = rng.roll_dice(1, self.map.height - h - 1) - 1;
+            let new_room = Rect::new(x, y, w, h);
+            let mut ok = true;
+            for other_room in self.map.rooms.iter() {
+                if new_room.intersect(other_room) {
+                    ok = false
+                }
+            }
+            if ok {
+                apply_room_to_map(&mut self.map, &new_room);
+
+                if !self.map.rooms.is_empty() {
+                    let (new_x, new_y) = new_room.center();
+                    let (prev_x, prev_y) = self.map.rooms[self.map.rooms.len() - 1].center();
+                    if rng.range(0, 2) == 1 {
+                        apply_horizontal_tunnel(&mut self.map, prev_x, new_x, prev_y);
+                        apply_vertical_tunnel(&mut self.map, prev_y, new_y, new_x);
+                    } else {
+                        apply_vertical_tunnel(&mut self.map, prev_y, new_y, prev_x);
+                        apply_horizontal_tunnel(&mut self.map, prev_x, new_x, new_y);
+                    }
+                }
+
+                self.map.rooms.push(new_room);
+            }
+        }
+
+        // Set stair position to center of last room.
+        let stairs_position = self.map.rooms[self.map.rooms.len() - 1].center();
+        let stairs_idx = self.map.get_index_at(stairs_position.0, stairs_position.1);
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+
+        let (start_x, start_y) = self.map.rooms[0].center();
+        self.starting_position = Position {
+            x: start_x,
+            y: start_y,
+        };
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Vec<usize>> {
+        rooms_to_regions(&self.map)
+    }
+
+    fn get_door_positions(&self) -> Vec<usize> {
+        door_positions(&self.map)
+    }
+}
+
+impl SimpleMapBuilder {
+    pub fn new(new_depth: i32) -> SimpleMapBuilder {
+        SimpleMapBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+        }
+    }
+}
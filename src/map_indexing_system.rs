@@ -1,4 +1,4 @@
-use super::{BlocksTile, Map, Position};
+use super::{BlocksTile, BlocksVisibility, EntryTrigger, Hidden, Map, Position};
 use specs::prelude::*;
 
 pub struct MapIndexingSystem {}
@@ -8,14 +8,19 @@ impl<'a> System<'a> for MapIndexingSystem {
         WriteExpect<'a, Map>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, BlocksVisibility>,
+        ReadStorage<'a, EntryTrigger>,
+        ReadStorage<'a, Hidden>,
         Entities<'a>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, view_blockers, triggers, hidden, entities) = data;
 
         map.populate_blocked();
         map.clear_content_index();
+        map.known_traps.clear();
+        map.view_blocked.clear();
         for (entity, position) in (&entities, &position).join() {
             let idx = map.get_index_at(position.x, position.y);
 
@@ -27,6 +32,13 @@ impl<'a> System<'a> for MapIndexingSystem {
             // Push the entity to the index slot
             // Entity is a copy type, so no need to clone (avoid moving out of ECS)
             map.tile_content[idx].push(entity);
+
+            if view_blockers.contains(entity) {
+                map.view_blocked.insert(idx);
+            }
+            if triggers.contains(entity) && !hidden.contains(entity) {
+                map.known_traps.insert(idx);
+            }
         }
     }
 }
@@ -1,11 +1,27 @@
 use crate::{
-    components::{DefenseBonus, Equipped, MeleePowerBonus, Position},
-    particle_system::ParticleBuilder,
+    components::{
+        Attributes, EquipmentBonus, Equipped, HungerClock, HungerState, MeleeWeapon, Skills,
+    },
+    effects::{particle, EffectQueue, EffectType, Targets},
+    gamesystem::{armor_class, attr_bonus, equipment_bonus, roll_damage, roll_to_hit, AttackRoll},
 };
 
-use super::{gamelog::GameLog, CombatStats, Name, SufferDamage, WantsToMelee};
+use super::{
+    gamelog::{GameLog, LogCategory, Logger},
+    CombatStats, Name, WantsToMelee,
+};
 use specs::prelude::*;
 
+/// Fists, for whoever has no weapon and no natural attack.
+const UNARMED: MeleeWeapon = MeleeWeapon {
+    damage_n_dice: 1,
+    damage_die_type: 4,
+    damage_bonus: 0,
+    hit_bonus: 0,
+};
+
+/// Resolves attacks with a d20 against the target's armor class, see
+/// `gamesystem::roll_to_hit`.
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
@@ -15,12 +31,14 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
-        WriteStorage<'a, SufferDamage>,
-        ReadStorage<'a, MeleePowerBonus>,
-        ReadStorage<'a, DefenseBonus>,
+        ReadStorage<'a, MeleeWeapon>,
+        ReadStorage<'a, EquipmentBonus>,
         ReadStorage<'a, Equipped>,
-        WriteExpect<'a, ParticleBuilder>,
-        ReadStorage<'a, Position>,
+        WriteExpect<'a, EffectQueue>,
+        ReadStorage<'a, HungerClock>,
+        ReadStorage<'a, Attributes>,
+        ReadStorage<'a, Skills>,
+        WriteExpect<'a, rltk::RandomNumberGenerator>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -30,12 +48,14 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut wants_melee,
             names,
             combat_stats,
-            mut inflict_damage,
-            melee_power_bonus,
-            defense_bonuses,
+            melee_weapons,
+            bonuses,
             equipped,
-            mut particle_builder,
-            positions,
+            mut effects,
+            hunger_clock,
+            attributes,
+            skills,
+            mut rng,
         ) = data;
 
         for (entity, wants_melee, name, stats) in
@@ -45,15 +65,6 @@ impl<'a> System<'a> for MeleeCombatSystem {
                 return;
             }
 
-            let mut offensive_bonus = 0;
-            for (_item_entity, power_bonus, equipped_by) in
-                (&entities, &melee_power_bonus, &equipped).join()
-            {
-                if equipped_by.owner == entity {
-                    offensive_bonus += power_bonus.power;
-                }
-            }
-
             let target_stats = combat_stats.get(wants_melee.target).unwrap();
             if target_stats.hp <= 0 {
                 return;
@@ -61,42 +72,76 @@ impl<'a> System<'a> for MeleeCombatSystem {
 
             let target_name = names.get(wants_melee.target).unwrap();
 
-            let mut defensive_bonus = 0;
-            for (_item_entity, defense_bonus, equipped_by) in
-                (&entities, &defense_bonuses, &equipped).join()
-            {
-                if equipped_by.owner == wants_melee.target {
-                    defensive_bonus += defense_bonus.defense;
+            // a wielded weapon, else the attacker's natural one
+            let mut weapon = melee_weapons.get(entity).unwrap_or(&UNARMED);
+            for (melee_weapon, equipped_by) in (&melee_weapons, &equipped).join() {
+                if equipped_by.owner == entity {
+                    weapon = melee_weapon;
                 }
             }
 
-            let pos = positions.get(wants_melee.target);
-            if let Some(pos) = pos {
-                particle_builder.request(
-                    pos.x,
-                    pos.y,
-                    rltk::RGB::named(rltk::ORANGE),
-                    rltk::RGB::named(rltk::BLACK),
-                    rltk::to_cp437('‼'),
-                    200.0,
-                );
+            let might_bonus = attributes.get(entity).map_or(0, |a| attr_bonus(a.might));
+            let mut hit_bonus =
+                might_bonus + skills.get(entity).map_or(0, |s| s.melee) + weapon.hit_bonus;
+            if let Some(clock) = hunger_clock.get(entity) {
+                if clock.state == HungerState::WellFed {
+                    hit_bonus += 1;
+                }
             }
 
-            let damage = i32::max(
-                0,
-                (stats.power + offensive_bonus) - (target_stats.defense + defensive_bonus),
+            let armor_class = armor_class(
+                wants_melee.target,
+                &attributes,
+                &skills,
+                &equipped,
+                &bonuses,
             );
-            if damage == 0 {
-                log.entries.push(format!(
-                    "{} is unable to hurt {}",
-                    &name.name, &target_name.name
-                ));
-            } else {
-                log.entries.push(format!(
-                    "{} hurts {}, for {} hp",
-                    &name.name, &target_name.name, damage
-                ));
-                SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+
+            let target = Targets::Single {
+                target: wants_melee.target,
+            };
+            effects.push(Some(entity), particle('‼', rltk::ORANGE), target.clone());
+
+            let roll = roll_to_hit(&mut rng, hit_bonus, armor_class);
+            match roll {
+                AttackRoll::Fumble => {
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("fumbles, missing")
+                        .name(&target_name.name)
+                        .log(&mut log);
+                }
+                AttackRoll::Miss => {
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("misses")
+                        .name(&target_name.name)
+                        .log(&mut log);
+                }
+                AttackRoll::Hit | AttackRoll::Critical => {
+                    let damage = roll_damage(
+                        &mut rng,
+                        &roll,
+                        weapon.damage_n_dice,
+                        weapon.damage_die_type,
+                        weapon.damage_bonus
+                            + might_bonus
+                            + equipment_bonus(entity, &equipped, &bonuses).power,
+                    );
+                    let verb = match roll {
+                        AttackRoll::Critical => "lands a critical hit on",
+                        _ => "hurts",
+                    };
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append(verb)
+                        .name(&target_name.name)
+                        .append(", for")
+                        .damage(damage)
+                        .append("hp")
+                        .log(&mut log);
+                    effects.push(Some(entity), EffectType::Damage { amount: damage }, target);
+                }
             }
         }
 
@@ -1,7 +1,30 @@
-use super::{Confusion, Map, Monster, Name, Position, RunState, Viewshed, WantsToMelee};
-use rltk::{console, Point};
+use super::{
+    flow_map_system::PlayerFlowMap,
+    gamelog::{GameLog, LogCategory, Logger},
+    initiative_system::{end_turn, AIMED_TURN_COST, TURN_COST},
+    ranged_combat_system::projectile_flight,
+    raws::{faction_reaction, RawMaster, Reaction, RAWS},
+    AiState, Ammunition, Brain, CombatStats, Energy, EntityMoved, Equipped, Faction, InBackpack,
+    Map, MyTurn, Name, Position, RangedWeapon, RunState, SpellBook, SpellEffect, StatusEffects,
+    StatusKind, Viewshed, WantsToCast, WantsToMelee, WantsToShoot,
+};
+use rltk::{BaseMap, Point};
 use specs::prelude::*;
 
+/// Chance in this many that an idle monster stays where it is.
+const IDLE_STAY_ONE_IN: i32 = 3;
+/// How far the player's allies let them go before following.
+const FOLLOW_DISTANCE: f32 = 3.0;
+
+/// Moves the monsters whose turn it is, according to the state of their
+/// `Brain`: they wander until they see a creature their faction attacks, then
+/// chase it, look where it was last seen once they lose sight of it, and run
+/// from what they fear or, for the cowardly ones, when badly hurt. Archers and
+/// casters keep their distance while they chase, shooting or casting whenever
+/// they can. The player's allies follow them around.
+///
+/// Going for the player, or away from them, follows the shared
+/// `PlayerFlowMap`; other creatures are chased with A*.
 pub struct MonsterAI {}
 
 impl<'a> System<'a> for MonsterAI {
@@ -12,10 +35,26 @@ impl<'a> System<'a> for MonsterAI {
         ReadExpect<'a, RunState>,
         Entities<'a>,
         WriteStorage<'a, Viewshed>,
-        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Faction>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, WantsToMelee>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, StatusEffects>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, MyTurn>,
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, Brain>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, rltk::RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, SpellBook>,
+        WriteStorage<'a, WantsToCast>,
+        WriteStorage<'a, WantsToShoot>,
+        ReadStorage<'a, RangedWeapon>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Ammunition>,
+        WriteExpect<'a, PlayerFlowMap>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -26,62 +65,389 @@ impl<'a> System<'a> for MonsterAI {
             runstate,
             entities,
             mut viewshed,
-            monster,
+            factions,
             mut position,
             mut wants_to_melee,
-            mut confused,
+            statuses,
+            mut energy,
+            mut my_turn,
+            mut entity_moved,
+            mut brains,
+            combat_stats,
+            names,
+            mut rng,
+            mut log,
+            mut spellbooks,
+            mut wants_to_cast,
+            mut wants_to_shoot,
+            ranged_weapons,
+            equipped,
+            backpack,
+            ammunition,
+            mut flow,
         ) = data;
 
-        if *runstate != RunState::MonsterTurn {
+        if *runstate != RunState::Ticking {
             return;
         }
 
-        for (entity, mut viewshed, _monster, mut pos) in
-            (&entities, &mut viewshed, &monster, &mut position).join()
+        let raws = RAWS.lock().unwrap();
+        let mut turn_done = Vec::new();
+        for (entity, viewshed, pos, _energy, _my_turn, brain, stats) in (
+            &entities,
+            &mut viewshed,
+            &mut position,
+            &energy,
+            &my_turn,
+            &mut brains,
+            &combat_stats,
+        )
+            .join()
         {
-            let mut can_act = true;
-            let is_confused = confused.get_mut(entity);
-            if let Some(i_am_confused) = is_confused {
-                i_am_confused.turns -= 1;
-                if i_am_confused.turns < 1 {
-                    confused.remove(entity);
+            if let Some(book) = spellbooks.get_mut(entity) {
+                for spell in book.spells.iter_mut() {
+                    spell.ready_in = i32::max(0, spell.ready_in - 1);
                 }
-                can_act = false;
             }
 
-            if !can_act {
-                return;
+            // confusion costs the whole turn
+            if statuses
+                .get(entity)
+                .is_some_and(|s| s.has(StatusKind::Confusion))
+            {
+                turn_done.push((entity, TURN_COST));
+                continue;
             }
 
-            let distance =
-                rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+            let here = Point::new(pos.x, pos.y);
+            let here_idx = map.get_index_at(here.x, here.y);
+            let faction = factions.get(entity).map_or("", |f| f.name.as_str());
+            let (enemy, threat) = look_around(
+                &map,
+                viewshed,
+                here,
+                faction,
+                &factions,
+                &combat_stats,
+                &raws,
+            );
+            let hurt = stats.hp * 100 < stats.max_hp * brain.flee_below;
+            // what it runs from: what it fears, or what it fights once badly hurt
+            let flee_from = threat.or(enemy.filter(|_| hurt));
+            let next_state = match (enemy, flee_from) {
+                (_, Some(_)) => AiState::Fleeing,
+                (Some((_, target_pos)), None) => AiState::Chasing {
+                    x: target_pos.x,
+                    y: target_pos.y,
+                },
+                (None, None) => match brain.state {
+                    AiState::Chasing { x, y } => AiState::Searching {
+                        x,
+                        y,
+                        turns: brain.memory,
+                    },
+                    AiState::Searching { x, y, turns } if turns > 0 => {
+                        AiState::Searching { x, y, turns }
+                    }
+                    _ => AiState::Idle,
+                },
+            };
+            if next_state == AiState::Fleeing && brain.state != AiState::Fleeing {
+                let idx = map.get_index_at(pos.x, pos.y);
+                if let (true, Some(name)) = (map.visible_tiles[idx], names.get(entity)) {
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("turns to flee!")
+                        .log(&mut log);
+                }
+            }
+            brain.state = next_state;
+            let mut cost = TURN_COST;
 
-            if distance < 1.5 {
+            let next_to = |target_pos: Point| {
+                rltk::DistanceAlg::Pythagoras.distance2d(here, target_pos) < 1.5
+            };
+            let attack = |wants_to_melee: &mut WriteStorage<WantsToMelee>, target: Entity| {
                 wants_to_melee
-                    .insert(
-                        entity,
-                        WantsToMelee {
-                            target: *player_entity,
-                        },
-                    )
+                    .insert(entity, WantsToMelee { target })
                     .expect("Unable to create wants to melee");
-            } else if viewshed.visible_tiles.contains(&*player_pos) {
-                let path = rltk::a_star_search(
-                    map.get_index_at(pos.x, pos.y) as i32,
-                    map.get_index_at(player_pos.x, player_pos.y) as i32,
-                    &mut *map,
-                );
-                if path.success && path.steps.len() > 1 {
-                    let mut idx = map.get_index_at(pos.x, pos.y);
-                    map.blocked[idx] = false;
-                    let (x, y) = map.get_xy_from_idx(path.steps[1]);
-                    pos.x = x;
-                    pos.y = y;
-                    idx = map.get_index_at(pos.x, pos.y);
-                    map.blocked[idx] = true;
-                    viewshed.dirty = true;
+            };
+
+            let destination = match (brain.state, enemy, flee_from) {
+                (AiState::Fleeing, _, Some((threat, threat_pos))) => {
+                    let step = if threat == *player_entity {
+                        flow.step_away(&map, here_idx)
+                    } else {
+                        flee_step(&map, here, threat_pos)
+                    };
+                    // cornered, it fights back
+                    if step.is_none() && next_to(threat_pos) {
+                        attack(&mut wants_to_melee, threat);
+                    }
+                    step
+                }
+                (AiState::Chasing { x, y }, Some((target, target_pos)), _) => {
+                    let adjacent = next_to(target_pos);
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(here, target_pos);
+                    let clear_shot =
+                        projectile_flight(&map, &combat_stats, here, target_pos).1 == Some(target);
+                    let weapon_range = loaded_weapon_range(
+                        entity,
+                        &entities,
+                        &names,
+                        &ranged_weapons,
+                        &equipped,
+                        &backpack,
+                        &ammunition,
+                    );
+                    let book = spellbooks.get(entity);
+                    let has_bolt = book.is_some_and(|book| {
+                        book.spells
+                            .iter()
+                            .any(|spell| matches!(spell.effect, SpellEffect::Bolt { .. }))
+                    });
+                    let spell = book.and_then(|book| {
+                        book.spells.iter().enumerate().find_map(|(i, spell)| {
+                            let in_range = distance <= spell.range as f32;
+                            let target = match &spell.effect {
+                                _ if spell.ready_in > 0 => None,
+                                SpellEffect::Bolt { .. } => {
+                                    (in_range && clear_shot).then_some(target)
+                                }
+                                SpellEffect::HealAlly { .. } => hurt_ally(
+                                    &map,
+                                    viewshed,
+                                    here,
+                                    spell.range,
+                                    faction,
+                                    &factions,
+                                    &combat_stats,
+                                ),
+                                SpellEffect::Summon { .. } => {
+                                    (!map.get_available_exits(here_idx).is_empty())
+                                        .then_some(entity)
+                                }
+                            };
+                            target.map(|target| (i, target))
+                        })
+                    });
+
+                    if let Some((spell, target)) = spell {
+                        wants_to_cast
+                            .insert(entity, WantsToCast { spell, target })
+                            .expect("Unable to create wants to cast");
+                        cost = AIMED_TURN_COST;
+                        None
+                    } else if adjacent && (has_bolt || weapon_range.is_some()) {
+                        // too close for comfort, back off if it can
+                        let step = flee_step(&map, here, target_pos);
+                        if step.is_none() {
+                            attack(&mut wants_to_melee, target);
+                        }
+                        step
+                    } else if adjacent {
+                        attack(&mut wants_to_melee, target);
+                        None
+                    } else if weapon_range.is_some_and(|range| distance <= range as f32)
+                        && clear_shot
+                    {
+                        wants_to_shoot
+                            .insert(entity, WantsToShoot { target: target_pos })
+                            .expect("Unable to create wants to shoot");
+                        cost = AIMED_TURN_COST;
+                        None
+                    } else if target == *player_entity {
+                        flow.step_toward(&map, here_idx)
+                    } else {
+                        path_step(&mut map, here, Point::new(x, y))
+                    }
+                }
+                (AiState::Searching { x, y, turns }, _, _) => {
+                    let hunts_player = factions.get(*player_entity).is_some_and(|player| {
+                        faction_reaction(faction, &player.name, &raws) == Reaction::Attack
+                    });
+                    let relentless = brain.relentless && hunts_player;
+                    let target = if relentless {
+                        *player_pos
+                    } else {
+                        Point::new(x, y)
+                    };
+                    brain.state = if target == here || turns <= 1 {
+                        AiState::Idle
+                    } else {
+                        AiState::Searching {
+                            x,
+                            y,
+                            turns: turns - 1,
+                        }
+                    };
+                    if relentless {
+                        flow.step_toward(&map, here_idx)
+                    } else {
+                        path_step(&mut map, here, target)
+                    }
                 }
+                _ => {
+                    // the player's allies stay close to them
+                    let follows_player = factions
+                        .get(*player_entity)
+                        .is_some_and(|player| player.name == faction);
+                    let far = rltk::DistanceAlg::Pythagoras.distance2d(here, *player_pos)
+                        > FOLLOW_DISTANCE;
+                    if follows_player && far {
+                        flow.step_toward(&map, here_idx)
+                    } else {
+                        wander_step(&map, here, &mut rng)
+                    }
+                }
+            };
+
+            if let Some(idx) = destination {
+                let old_idx = map.get_index_at(pos.x, pos.y);
+                map.blocked[old_idx] = false;
+                (pos.x, pos.y) = map.get_xy_from_idx(idx);
+                map.blocked[idx] = true;
+                viewshed.dirty = true;
+                entity_moved
+                    .insert(entity, EntityMoved {})
+                    .expect("Unable to insert entity moved");
             }
+            turn_done.push((entity, cost));
+        }
+
+        for (entity, cost) in turn_done {
+            end_turn(entity, cost, &mut energy, &mut my_turn);
         }
     }
 }
+
+/// The first tile of the way to `target`.
+fn path_step(map: &mut Map, from: Point, target: Point) -> Option<usize> {
+    let path = rltk::a_star_search(
+        map.get_index_at(from.x, from.y) as i32,
+        map.get_index_at(target.x, target.y) as i32,
+        &mut *map,
+    );
+    if path.success && path.steps.len() > 1 {
+        Some(path.steps[1])
+    } else {
+        None
+    }
+}
+
+/// The free neighbouring tile furthest from `threat`, if it is further than
+/// where the monster stands.
+fn flee_step(map: &Map, from: Point, threat: Point) -> Option<usize> {
+    let distance = |idx: usize| {
+        let (x, y) = map.get_xy_from_idx(idx);
+        rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x, y), threat)
+    };
+    let here = map.get_index_at(from.x, from.y);
+    map.get_available_exits(here)
+        .into_iter()
+        .map(|(idx, _)| idx)
+        .filter(|idx| distance(*idx) > distance(here))
+        .max_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+}
+
+/// The range of the ranged weapon `entity` has equipped, if it has ammunition
+/// for it.
+fn loaded_weapon_range(
+    entity: Entity,
+    entities: &Entities,
+    names: &ReadStorage<Name>,
+    ranged_weapons: &ReadStorage<RangedWeapon>,
+    equipped: &ReadStorage<Equipped>,
+    backpack: &ReadStorage<InBackpack>,
+    ammunition: &ReadStorage<Ammunition>,
+) -> Option<i32> {
+    let (weapon, _) = (ranged_weapons, equipped)
+        .join()
+        .find(|(_, equipped_by)| equipped_by.owner == entity)?;
+    let owns = |item: Entity| {
+        backpack.get(item).is_some_and(|b| b.owner == entity)
+            || equipped.get(item).is_some_and(|e| e.owner == entity)
+    };
+    (entities, ammunition, names)
+        .join()
+        .any(|(item, _, name)| name.name == weapon.ammo && owns(item))
+        .then_some(weapon.range)
+}
+
+/// The most hurt other member of `faction` in sight and within `range`, if any
+/// is down to half its health.
+fn hurt_ally(
+    map: &Map,
+    viewshed: &Viewshed,
+    from: Point,
+    range: i32,
+    faction: &str,
+    factions: &ReadStorage<Faction>,
+    combat_stats: &ReadStorage<CombatStats>,
+) -> Option<Entity> {
+    viewshed
+        .visible_tiles
+        .iter()
+        .filter(|tile| **tile != from)
+        .filter(|tile| rltk::DistanceAlg::Pythagoras.distance2d(from, **tile) <= range as f32)
+        .flat_map(|tile| map.tile_content[map.get_index_at(tile.x, tile.y)].iter())
+        .filter(|other| factions.get(**other).is_some_and(|f| f.name == faction))
+        .filter_map(|other| combat_stats.get(*other).map(|stats| (*other, stats)))
+        .filter(|(_, stats)| stats.hp * 2 <= stats.max_hp)
+        .min_by_key(|(_, stats)| stats.hp * 100 / stats.max_hp)
+        .map(|(other, _)| other)
+}
+
+/// The nearest creature in sight that `faction` attacks, and the nearest one
+/// it runs away from.
+#[allow(clippy::type_complexity)]
+fn look_around(
+    map: &Map,
+    viewshed: &Viewshed,
+    from: Point,
+    faction: &str,
+    factions: &ReadStorage<Faction>,
+    combat_stats: &ReadStorage<CombatStats>,
+    raws: &RawMaster,
+) -> (Option<(Entity, Point)>, Option<(Entity, Point)>) {
+    let mut enemy: Option<(Entity, Point, f32)> = None;
+    let mut threat: Option<(Entity, Point, f32)> = None;
+    for tile in viewshed.visible_tiles.iter().filter(|tile| **tile != from) {
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(from, *tile);
+        for other in map.tile_content[map.get_index_at(tile.x, tile.y)].iter() {
+            let Some(other_faction) = factions.get(*other) else {
+                continue;
+            };
+            if !combat_stats.contains(*other) {
+                continue;
+            }
+            let nearest = match faction_reaction(faction, &other_faction.name, raws) {
+                Reaction::Attack => &mut enemy,
+                Reaction::Flee => &mut threat,
+                Reaction::Ignore => continue,
+            };
+            if nearest.is_none_or(|(_, _, d)| distance < d) {
+                *nearest = Some((*other, *tile, distance));
+            }
+        }
+    }
+    let strip = |found: Option<(Entity, Point, f32)>| found.map(|(e, p, _)| (e, p));
+    (strip(enemy), strip(threat))
+}
+
+/// A random free neighbouring tile, away from known traps. Sometimes none.
+fn wander_step(map: &Map, from: Point, rng: &mut rltk::RandomNumberGenerator) -> Option<usize> {
+    if rng.roll_dice(1, IDLE_STAY_ONE_IN) == 1 {
+        return None;
+    }
+    let exits: Vec<usize> = map
+        .get_available_exits(map.get_index_at(from.x, from.y))
+        .into_iter()
+        .map(|(idx, _)| idx)
+        .filter(|idx| !map.known_traps.contains(idx))
+        .collect();
+    if exits.is_empty() {
+        return None;
+    }
+    Some(exits[rng.range(0, exits.len())])
+}
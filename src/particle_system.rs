@@ -11,6 +11,7 @@ struct ParticleRequest {
     bg: RGB,
     glyph: rltk::FontCharType,
     lifetime: f32,
+    delay: f32,
 }
 
 pub struct ParticleBuilder {
@@ -40,8 +41,30 @@ impl ParticleBuilder {
             bg,
             glyph,
             lifetime,
+            delay: 0.0,
         })
     }
+
+    /// One particle on each tile of `path` in turn, like a projectile in flight.
+    pub fn request_path(
+        &mut self,
+        path: &[rltk::Point],
+        fg: RGB,
+        glyph: rltk::FontCharType,
+        ms_per_tile: f32,
+    ) {
+        for (i, point) in path.iter().enumerate() {
+            self.requests.push(ParticleRequest {
+                x: point.x,
+                y: point.y,
+                fg,
+                bg: RGB::named(rltk::BLACK),
+                glyph,
+                lifetime: ms_per_tile,
+                delay: i as f32 * ms_per_tile,
+            })
+        }
+    }
 }
 
 pub struct ParticleSpawnSystem {}
@@ -86,6 +109,7 @@ impl<'a> System<'a> for ParticleSpawnSystem {
                     p,
                     ParticleLifetime {
                         lifetime_ms: new_particle.lifetime,
+                        delay_ms: new_particle.delay,
                     },
                 )
                 .expect("Failed to insert particle lifetime on new particle");
@@ -101,6 +125,10 @@ pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
         let mut particles = ecs.write_storage::<ParticleLifetime>();
         let entities = ecs.entities();
         for (entity, mut particle) in (&entities, &mut particles).join() {
+            if particle.delay_ms > 0.0 {
+                particle.delay_ms -= ctx.frame_time_ms;
+                continue;
+            }
             particle.lifetime_ms -= ctx.frame_time_ms;
             if particle.lifetime_ms < 0.0 {
                 dead_particles.push(entity);
@@ -1,25 +1,65 @@
 use crate::{
-    components::{Item, WantsToPickupItem, Monster},
-    gamelog::GameLog,
-    map::{TileType, MAPHEIGHT, MAPWIDTH},
-    Aiming,
+    components::{
+        Attributes, BlocksTile, BlocksVisibility, Door, EntityMoved, Equipped, Faction, Hidden,
+        HungerClock, HungerState, InBackpack, Item, Key, Monster, RangedWeapon, Renderable,
+        StatusEffects, StatusKind, WantsToPickupItem,
+    },
+    gamelog::{GameLog, LogCategory, Logger},
+    gamesystem::spots_hidden,
+    gui,
+    map::{TileType, KNOWN_TRAP_COST, MAPHEIGHT, MAPWIDTH},
+    raws::{faction_reaction, Reaction, RAWS},
+    saveload_system, Aiming, AutoWalk,
 };
 
-use super::{CombatStats, Map, Player, Position, RunState, State, Viewshed, WantsToMelee};
-use rltk::{Point, Rltk};
+use super::{CombatStats, Map, Name, Player, Position, RunState, State, Viewshed, WantsToMelee};
+use rltk::{BaseMap, DijkstraMap, Point, Rltk};
 use specs::prelude::*;
 use std::cmp::{max, min};
 
+/// Where a confused player may stagger to instead.
+const DIRECTIONS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const OPEN_DOOR_GLYPH: char = '/';
+
+/// How far searching reaches.
+const SEARCH_RADIUS: f32 = 3.0;
+/// Added to perception rolls when searching rather than just looking around.
+const SEARCH_BONUS: i32 = 10;
+/// Further than any walk across the map.
+const MAX_WALK: f32 = 2000.0;
+
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+    let (delta_x, delta_y) = stagger_if_confused(delta_x, delta_y, ecs);
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
 
-    let map = ecs.fetch::<Map>();
+    let mut map = ecs.fetch_mut::<Map>();
 
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut entity_moved = ecs.write_storage::<EntityMoved>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut blocks_tile = ecs.write_storage::<BlocksTile>();
+    let mut blocks_visibility = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let keys = ecs.read_storage::<Key>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let factions = ecs.read_storage::<Faction>();
+    let names = ecs.read_storage::<Name>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    let mut swap_with = None;
 
     // Since we join the two, it will only run on those that
     // have both position and player components.
@@ -38,6 +78,18 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
         for potential_target in map.tile_content[destination_index].iter() {
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
+                let hostile = match (factions.get(entity), factions.get(*potential_target)) {
+                    (Some(mine), Some(theirs)) => {
+                        let raws = RAWS.lock().unwrap();
+                        faction_reaction(&mine.name, &theirs.name, &raws) == Reaction::Attack
+                    }
+                    _ => true,
+                };
+                if !hostile {
+                    // the others make way
+                    swap_with = Some((*potential_target, Position { x: pos.x, y: pos.y }));
+                    break;
+                }
                 wants_to_melee
                     .insert(
                         entity,
@@ -50,10 +102,47 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             }
         }
 
-        if !map.blocked[destination_index] {
+        // bumping into a closed door opens it
+        for potential_door in map.tile_content[destination_index].iter() {
+            let Some(door) = doors.get_mut(*potential_door).filter(|door| !door.open) else {
+                continue;
+            };
+            if door.locked {
+                let key = (&entities, &keys, &backpack)
+                    .join()
+                    .find(|(_, _, in_backpack)| in_backpack.owner == entity)
+                    .map(|(key, _, _)| key);
+                let Some(key) = key else {
+                    Logger::new(LogCategory::System)
+                        .append("The door is locked.")
+                        .log(&mut gamelog);
+                    return;
+                };
+                entities.delete(key).expect("Unable to delete used key");
+                door.locked = false;
+                Logger::new(LogCategory::System)
+                    .append("You unlock the door.")
+                    .log(&mut gamelog);
+            }
+            door.open = true;
+            blocks_tile.remove(*potential_door);
+            blocks_visibility.remove(*potential_door);
+            if let Some(render) = renderables.get_mut(*potential_door) {
+                render.glyph = rltk::to_cp437(OPEN_DOOR_GLYPH);
+            }
+            map.blocked[destination_index] = false;
+            map.view_blocked.remove(&destination_index);
+            viewshed.dirty = true;
+            return;
+        }
+
+        if !map.blocked[destination_index] || swap_with.is_some() {
             pos.x = min(MAPWIDTH as i32 - 1, max(0, pos.x + delta_x));
             pos.y = min(MAPHEIGHT as i32 - 1, max(0, pos.y + delta_y));
             viewshed.dirty = true;
+            entity_moved
+                .insert(entity, EntityMoved {})
+                .expect("Unable to insert entity moved");
 
             // Write new_pos to storage for everyone to access it
             let mut ppos = ecs.write_resource::<Point>();
@@ -65,9 +154,45 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             aiming_pos.y = pos.y - 1;
         }
     }
+
+    if let Some((other, old_pos)) = swap_with {
+        if let Some(name) = names.get(other) {
+            Logger::new(LogCategory::System)
+                .append("You swap places with the")
+                .name(&name.name)
+                .append(".")
+                .log(&mut gamelog);
+        }
+        positions
+            .insert(other, old_pos)
+            .expect("Unable to swap places");
+        if let Some(viewshed) = viewsheds.get_mut(other) {
+            viewshed.dirty = true;
+        }
+        entity_moved
+            .insert(other, EntityMoved {})
+            .expect("Unable to insert entity moved");
+    }
+}
+
+fn stagger_if_confused(delta_x: i32, delta_y: i32, ecs: &World) -> (i32, i32) {
+    let player_entity = ecs.fetch::<Entity>();
+    let confused = ecs
+        .read_storage::<StatusEffects>()
+        .get(*player_entity)
+        .is_some_and(|s| s.has(StatusKind::Confusion));
+    if !confused {
+        return (delta_x, delta_y);
+    }
+    let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+    DIRECTIONS[rng.range(0, DIRECTIONS.len())]
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    if ctx.left_click {
+        return travel_to(&mut gs.ecs, ctx.mouse_point());
+    }
+
     match ctx.key {
         None => {
             return RunState::AwaitingInput;
@@ -96,20 +221,45 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             rltk::VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
 
             rltk::VirtualKeyCode::G => get_item(&mut gs.ecs),
+            rltk::VirtualKeyCode::F => return aim_ranged_weapon(&mut gs.ecs),
 
             rltk::VirtualKeyCode::I => return RunState::ShowInventory,
 
             rltk::VirtualKeyCode::D => return RunState::ShowDropItem,
             rltk::VirtualKeyCode::R => return RunState::ShowRemoveItem,
+            rltk::VirtualKeyCode::C => return RunState::ShowCharacter,
+            rltk::VirtualKeyCode::M => {
+                return RunState::ShowLog {
+                    scroll: 0,
+                    filter: None,
+                }
+            }
 
-            rltk::VirtualKeyCode::Escape => return RunState::SaveGame,
+            rltk::VirtualKeyCode::Escape => {
+                saveload_system::refresh_save_slots(&gs.ecs);
+                return RunState::ShowSaveSlots {
+                    mode: gui::SlotMenuMode::Save,
+                    selection: 0,
+                };
+            }
             rltk::VirtualKeyCode::S => return skip_turn(&mut gs.ecs),
+            rltk::VirtualKeyCode::X => search(&mut gs.ecs),
+            rltk::VirtualKeyCode::O => {
+                *gs.ecs.write_resource::<AutoWalk>() = AutoWalk::Explore;
+                return RunState::AutoWalking;
+            }
+            rltk::VirtualKeyCode::T => return travel_to_stairs(&mut gs.ecs),
 
             rltk::VirtualKeyCode::Period => {
                 if try_next_level(&mut gs.ecs) {
                     return RunState::NextLevel;
                 }
             }
+            rltk::VirtualKeyCode::Comma => {
+                if try_previous_level(&mut gs.ecs) {
+                    return RunState::PreviousLevel;
+                }
+            }
 
             _ => {
                 return RunState::AwaitingInput;
@@ -136,9 +286,9 @@ fn get_item(ecs: &mut World) {
     }
 
     match target_item {
-        None => gamelog
-            .entries
-            .push("There is nothing to pick up.".to_string()),
+        None => Logger::new(LogCategory::System)
+            .append("There is nothing to pick up.")
+            .log(&mut gamelog),
         Some(item) => {
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
             pickup
@@ -154,6 +304,74 @@ fn get_item(ecs: &mut World) {
     }
 }
 
+/// Looks for hidden things nearby, with a better chance than in passing.
+fn search(ecs: &mut World) {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let intelligence = ecs
+        .read_storage::<Attributes>()
+        .get(*player_entity)
+        .map_or(10, |a| a.intelligence);
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let mut hidden = ecs.write_storage::<Hidden>();
+    let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut found = false;
+    let Some(viewshed) = viewsheds.get(*player_entity) else {
+        return;
+    };
+    for tile in viewshed.visible_tiles.iter() {
+        if rltk::DistanceAlg::Pythagoras.distance2d(*tile, player_pos) > SEARCH_RADIUS {
+            continue;
+        }
+        let idx = map.get_index_at(tile.x, tile.y);
+        for entity in map.tile_content[idx].iter() {
+            if hidden.contains(*entity) && spots_hidden(&mut rng, intelligence, SEARCH_BONUS) {
+                hidden.remove(*entity);
+                found = true;
+                if let Some(name) = names.get(*entity) {
+                    Logger::new(LogCategory::System)
+                        .append("You spot a")
+                        .item(&name.name)
+                        .append(".")
+                        .log(&mut gamelog);
+                }
+            }
+        }
+    }
+    if !found {
+        Logger::new(LogCategory::System)
+            .append("You search, but find nothing.")
+            .log(&mut gamelog);
+    }
+}
+
+/// Starts targeting with the wielded ranged weapon, if there is one.
+fn aim_ranged_weapon(ecs: &mut World) -> RunState {
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let ranged_weapons = ecs.read_storage::<RangedWeapon>();
+    let equipped = ecs.read_storage::<Equipped>();
+
+    for (weapon_entity, weapon, equipped_by) in (&entities, &ranged_weapons, &equipped).join() {
+        if equipped_by.owner == *player_entity {
+            return RunState::ShowTargeting {
+                range: weapon.range,
+                item: weapon_entity,
+            };
+        }
+    }
+
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    Logger::new(LogCategory::System)
+        .append("You have no ranged weapon to fire.")
+        .log(&mut gamelog);
+    RunState::AwaitingInput
+}
+
 fn player_on_position(player_pos: &Point, other_pos: &Position) -> bool {
     player_pos.x == other_pos.x && player_pos.y == other_pos.y
 }
@@ -166,30 +384,68 @@ pub fn try_next_level(ecs: &mut World) -> bool {
         true
     } else {
         let mut gamelog = ecs.fetch_mut::<GameLog>();
-        gamelog.entries.push("There is no stairs here".to_string());
+        Logger::new(LogCategory::System)
+            .append("There is no stairs here")
+            .log(&mut gamelog);
         false
     }
 }
 
-fn skip_turn(ecs: &mut World) -> RunState {
+pub fn try_previous_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.get_index_at(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::UpStairs {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        Logger::new(LogCategory::System)
+            .append("There is no stairs up here")
+            .log(&mut gamelog);
+        false
+    }
+}
+
+/// A monster in the player's sight that would attack them, if any.
+fn hostile_in_sight(ecs: &World) -> Option<Entity> {
     let player_entity = ecs.fetch::<Entity>();
     let viewshed_comps = ecs.read_storage::<Viewshed>();
     let monsters = ecs.read_storage::<Monster>();
+    let factions = ecs.read_storage::<Faction>();
+    let raws = RAWS.lock().unwrap();
+    let player_faction = factions.get(*player_entity).map_or("", |f| f.name.as_str());
 
     let world_map_resource = ecs.fetch::<Map>();
 
-    let mut can_heal = true;
-    let viewshed = viewshed_comps.get(*player_entity).unwrap();
+    let viewshed = viewshed_comps.get(*player_entity)?;
     for tile in viewshed.visible_tiles.iter() {
         let idx = world_map_resource.get_index_at(tile.x, tile.y);
         for entitiy_id in world_map_resource.tile_content[idx].iter() {
-            let mob = monsters.get(*entitiy_id);
-            match mob {
-                None => {}
-                Some(_) => { can_heal = false }
+            let hostile = monsters.contains(*entitiy_id)
+                && factions.get(*entitiy_id).is_none_or(|faction| {
+                    faction_reaction(&faction.name, player_faction, &raws) == Reaction::Attack
+                });
+            if hostile {
+                return Some(*entitiy_id);
             }
         }
     }
+    None
+}
+
+fn skip_turn(ecs: &mut World) -> RunState {
+    let player_entity = ecs.fetch::<Entity>();
+
+    // no rest with an enemy in sight
+    let mut can_heal = hostile_in_sight(ecs).is_none();
+
+    // an empty stomach doesn't heal
+    let hunger_clocks = ecs.read_storage::<HungerClock>();
+    if let Some(clock) = hunger_clocks.get(*player_entity) {
+        if clock.state == HungerState::Hungry || clock.state == HungerState::Starving {
+            can_heal = false;
+        }
+    }
 
     if can_heal {
         let mut healths = ecs.write_storage::<CombatStats>();
@@ -199,3 +455,183 @@ fn skip_turn(ecs: &mut World) -> RunState {
 
     RunState::PlayerTurn
 }
+
+/// Starts travelling to `target`. Clicks outside the ground the player knows
+/// do nothing.
+fn travel_to(ecs: &mut World, target: Point) -> RunState {
+    {
+        let map = ecs.fetch::<Map>();
+        if target.x >= map.width || target.y >= map.height {
+            return RunState::AwaitingInput;
+        }
+        let idx = map.get_index_at(target.x, target.y);
+        if !map.revealed_tiles[idx] || map.tiles[idx] == TileType::Wall {
+            return RunState::AwaitingInput;
+        }
+    }
+    *ecs.write_resource::<AutoWalk>() = AutoWalk::Travel {
+        x: target.x,
+        y: target.y,
+    };
+    RunState::AutoWalking
+}
+
+/// Starts travelling to the stairs down, once the player has seen them.
+fn travel_to_stairs(ecs: &mut World) -> RunState {
+    let stairs = {
+        let map = ecs.fetch::<Map>();
+        (0..map.tiles.len())
+            .find(|idx| map.tiles[*idx] == TileType::DownStairs && map.revealed_tiles[*idx])
+            .map(|idx| map.get_xy_from_idx(idx))
+    };
+    match stairs {
+        Some((x, y)) => travel_to(ecs, Point::new(x, y)),
+        None => {
+            Logger::new(LogCategory::System)
+                .append("You haven't found the stairs down yet.")
+                .log(&mut ecs.fetch_mut::<GameLog>());
+            RunState::AwaitingInput
+        }
+    }
+}
+
+/// Takes the next step of the player's `AutoWalk`. The walk ends when a key is
+/// pressed or the mouse clicked, when a hostile monster is in sight and when
+/// there is nowhere left to go. Getting hurt ends it too, see `DamageSystem`.
+pub fn auto_walk(ecs: &mut World, interrupted: bool) -> RunState {
+    let walk = *ecs.fetch::<AutoWalk>();
+    let step = if interrupted {
+        None
+    } else if let Some(hostile) = hostile_in_sight(ecs) {
+        if let Some(name) = ecs.read_storage::<Name>().get(hostile) {
+            Logger::new(LogCategory::System)
+                .append("You stop, the")
+                .name(&name.name)
+                .append("is in sight.")
+                .log(&mut ecs.fetch_mut::<GameLog>());
+        }
+        None
+    } else {
+        match walk {
+            AutoWalk::Off => None,
+            AutoWalk::Explore => explore_step(ecs),
+            AutoWalk::Travel { x, y } => travel_step(ecs, Point::new(x, y)),
+        }
+    };
+
+    match step {
+        Some((delta_x, delta_y)) => {
+            try_move_player(delta_x, delta_y, ecs);
+            RunState::PlayerTurn
+        }
+        None => {
+            *ecs.write_resource::<AutoWalk>() = AutoWalk::Off;
+            RunState::AwaitingInput
+        }
+    }
+}
+
+/// The move towards the nearest tile the player hasn't seen yet, over the
+/// tiles they know.
+fn explore_step(ecs: &World) -> Option<(i32, i32)> {
+    let player_pos = *ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let known = KnownTiles::new(ecs, &map);
+    // the unseen tiles next to known ground, the others are further anyway
+    let frontier: Vec<usize> = (0..map.revealed_tiles.len())
+        .filter(|idx| !map.revealed_tiles[*idx] && !known.get_available_exits(*idx).is_empty())
+        .collect();
+    let flow = DijkstraMap::new(map.width, map.height, &frontier, &known, MAX_WALK);
+
+    let here = map.get_index_at(player_pos.x, player_pos.y);
+    let Some(next) =
+        DijkstraMap::find_lowest_exit(&flow, here, &known).filter(|idx| flow.map[*idx] < MAX_WALK)
+    else {
+        Logger::new(LogCategory::System)
+            .append("There is nothing left to explore.")
+            .log(&mut ecs.fetch_mut::<GameLog>());
+        return None;
+    };
+    let (x, y) = map.get_xy_from_idx(next);
+    Some((x - player_pos.x, y - player_pos.y))
+}
+
+/// The move along the shortest way to `target` over the tiles the player knows,
+/// none once there.
+fn travel_step(ecs: &World, target: Point) -> Option<(i32, i32)> {
+    let player_pos = *ecs.fetch::<Point>();
+    if player_pos == target {
+        return None;
+    }
+    let map = ecs.fetch::<Map>();
+    let known = KnownTiles::new(ecs, &map);
+    let path = rltk::a_star_search(
+        map.get_index_at(player_pos.x, player_pos.y),
+        map.get_index_at(target.x, target.y),
+        &known,
+    );
+    if !path.success || path.steps.len() < 2 {
+        Logger::new(LogCategory::System)
+            .append("You don't know the way there.")
+            .log(&mut ecs.fetch_mut::<GameLog>());
+        return None;
+    }
+    let (x, y) = map.get_xy_from_idx(path.steps[1]);
+    Some((x - player_pos.x, y - player_pos.y))
+}
+
+/// The map as the player knows it, for walking them around: the floors they
+/// have seen, through creatures and closed doors, but not through locked doors
+/// since walking never spends keys.
+struct KnownTiles<'a> {
+    map: &'a Map,
+    locked: Vec<usize>,
+}
+
+impl<'a> KnownTiles<'a> {
+    fn new(ecs: &World, map: &'a Map) -> KnownTiles<'a> {
+        let locked = (&ecs.read_storage::<Door>(), &ecs.read_storage::<Position>())
+            .join()
+            .filter(|(door, _)| door.locked)
+            .map(|(_, pos)| map.get_index_at(pos.x, pos.y))
+            .collect();
+        KnownTiles { map, locked }
+    }
+
+    fn walkable(&self, idx: usize) -> bool {
+        self.map.revealed_tiles[idx]
+            && self.map.tiles[idx] != TileType::Wall
+            && !self.locked.contains(&idx)
+    }
+}
+
+impl BaseMap for KnownTiles<'_> {
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let mut exits = rltk::SmallVec::new();
+        let (x, y) = self.map.get_xy_from_idx(idx);
+        for (delta_x, delta_y) in DIRECTIONS.iter() {
+            let (exit_x, exit_y) = (x + delta_x, y + delta_y);
+            if exit_x < 0 || exit_x >= self.map.width || exit_y < 0 || exit_y >= self.map.height {
+                continue;
+            }
+            let exit = self.map.get_index_at(exit_x, exit_y);
+            if !self.walkable(exit) {
+                continue;
+            }
+            let mut cost = if *delta_x != 0 && *delta_y != 0 {
+                1.45
+            } else {
+                1.0
+            };
+            if self.map.known_traps.contains(&exit) {
+                cost += KNOWN_TRAP_COST;
+            }
+            exits.push((exit, cost));
+        }
+        exits
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{
+    components::{
+        Ammunition, Attributes, CombatStats, EquipmentBonus, Equipped, InBackpack, Name, Position,
+        RangedWeapon, Skills, WantsToShoot,
+    },
+    effects::{EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
+    gamesystem::{armor_class, attr_bonus, roll_damage, roll_to_hit, AttackRoll},
+    map::Map,
+    particle_system::ParticleBuilder,
+    raws::{spawn_named_entity, SpawnType, RAWS},
+};
+use rltk::{BaseMap, Point};
+use specs::prelude::*;
+
+/// How long a projectile takes to cross a tile.
+const MS_PER_TILE: f32 = 40.0;
+
+/// Fires the shooter's equipped ranged weapon. The projectile flies along a
+/// Bresenham line and stops at the first creature or wall in its way, then
+/// falls to the floor where it can be picked up again.
+pub struct RangedCombatSystem {}
+
+impl<'a> System<'a> for RangedCombatSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, WantsToShoot>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, RangedWeapon>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, InBackpack>,
+        WriteStorage<'a, Ammunition>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Attributes>,
+        ReadStorage<'a, Skills>,
+        ReadStorage<'a, EquipmentBonus>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, ParticleBuilder>,
+        WriteExpect<'a, rltk::RandomNumberGenerator>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut log,
+            mut wants_shoot,
+            names,
+            positions,
+            map,
+            ranged_weapons,
+            equipped,
+            backpack,
+            mut ammunition,
+            combat_stats,
+            attributes,
+            skills,
+            bonuses,
+            mut effects,
+            mut particle_builder,
+            mut rng,
+            lazy,
+        ) = data;
+
+        for (entity, wants_shoot, name, pos) in (&entities, &wants_shoot, &names, &positions).join()
+        {
+            let Some((weapon, _)) = (&ranged_weapons, &equipped)
+                .join()
+                .find(|(_, equipped_by)| equipped_by.owner == entity)
+            else {
+                continue;
+            };
+
+            // from the backpack, or the equipped stack for throwing weapons
+            let owns = |item: Entity| {
+                backpack.get(item).is_some_and(|b| b.owner == entity)
+                    || equipped.get(item).is_some_and(|e| e.owner == entity)
+            };
+            let Some((ammo_entity, ammo, _)) = (&entities, &mut ammunition, &names)
+                .join()
+                .find(|(item, _, ammo_name)| ammo_name.name == weapon.ammo && owns(*item))
+            else {
+                Logger::new(LogCategory::Item)
+                    .name(&name.name)
+                    .append("has no")
+                    .item(&weapon.ammo)
+                    .append("left.")
+                    .log(&mut log);
+                continue;
+            };
+            ammo.count -= 1;
+            if ammo.count <= 0 {
+                entities
+                    .delete(ammo_entity)
+                    .expect("Unable to delete used up ammunition");
+            }
+
+            let start = Point::new(pos.x, pos.y);
+            let (flight, victim) =
+                projectile_flight(&map, &combat_stats, start, wants_shoot.target);
+            particle_builder.request_path(
+                &flight,
+                rltk::RGB::named(rltk::WHITE),
+                projectile_glyph(start, wants_shoot.target),
+                MS_PER_TILE,
+            );
+
+            if let Some(victim) = victim {
+                let target_name = &names.get(victim).unwrap().name;
+                let hit_bonus = attributes
+                    .get(entity)
+                    .map_or(0, |a| attr_bonus(a.quickness))
+                    + skills.get(entity).map_or(0, |s| s.ranged)
+                    + weapon.hit_bonus;
+                let armor_class = armor_class(victim, &attributes, &skills, &equipped, &bonuses);
+                let roll = roll_to_hit(&mut rng, hit_bonus, armor_class);
+                match roll {
+                    AttackRoll::Fumble => {
+                        Logger::new(LogCategory::Combat)
+                            .name(&name.name)
+                            .append("fumbles the shot at")
+                            .name(target_name)
+                            .log(&mut log);
+                    }
+                    AttackRoll::Miss => {
+                        Logger::new(LogCategory::Combat)
+                            .name(&name.name)
+                            .append("shoots at")
+                            .name(target_name)
+                            .append("and misses")
+                            .log(&mut log);
+                    }
+                    AttackRoll::Hit | AttackRoll::Critical => {
+                        let damage = roll_damage(
+                            &mut rng,
+                            &roll,
+                            weapon.damage_n_dice,
+                            weapon.damage_die_type,
+                            weapon.damage_bonus,
+                        );
+                        let verb = match roll {
+                            AttackRoll::Critical => "lands a critical shot on",
+                            _ => "shoots",
+                        };
+                        Logger::new(LogCategory::Combat)
+                            .name(&name.name)
+                            .append(verb)
+                            .name(target_name)
+                            .append(", for")
+                            .damage(damage)
+                            .append("hp")
+                            .log(&mut log);
+                        effects.push(
+                            Some(entity),
+                            EffectType::Damage { amount: damage },
+                            Targets::Single { target: victim },
+                        );
+                    }
+                }
+            }
+
+            let landing = flight.last().copied().unwrap_or(start);
+            let ammo_name = weapon.ammo.clone();
+            lazy.exec_mut(move |world| {
+                let fired = spawn_named_entity(
+                    &RAWS.lock().unwrap(),
+                    world.create_entity(),
+                    &ammo_name,
+                    SpawnType::AtPosition {
+                        x: landing.x,
+                        y: landing.y,
+                    },
+                );
+                if let Some(fired) = fired {
+                    if let Some(ammo) = world.write_storage::<Ammunition>().get_mut(fired) {
+                        ammo.count = 1;
+                    }
+                }
+            });
+        }
+
+        wants_shoot.clear();
+    }
+}
+
+/// The tiles a projectile crosses on its way from `start` to `target`, and the
+/// creature it hits. It stops at the first creature or wall in its way.
+pub fn projectile_flight(
+    map: &Map,
+    combat_stats: &ReadStorage<CombatStats>,
+    start: Point,
+    target: Point,
+) -> (Vec<Point>, Option<Entity>) {
+    let mut flight: Vec<Point> = Vec::new();
+    for point in rltk::line2d_bresenham(start, target).into_iter().skip(1) {
+        let idx = map.get_index_at(point.x, point.y);
+        if map.is_opaque(idx) {
+            break;
+        }
+        flight.push(point);
+        let victim = map.tile_content[idx]
+            .iter()
+            .find(|other| combat_stats.contains(**other))
+            .copied();
+        if victim.is_some() {
+            return (flight, victim);
+        }
+    }
+    (flight, None)
+}
+
+/// A line pointing the way the projectile flies.
+fn projectile_glyph(from: Point, to: Point) -> rltk::FontCharType {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let glyph = if dy.abs() * 2 < dx.abs() {
+        '-'
+    } else if dx.abs() * 2 < dy.abs() {
+        '|'
+    } else if (dx > 0) == (dy > 0) {
+        '\\'
+    } else {
+        '/'
+    };
+    rltk::to_cp437(glyph)
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct FactionInfo {
+    pub name: String,
+    /// How it treats each faction, `Default` standing for those not listed.
+    pub responses: HashMap<String, Reaction>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Reaction {
+    Ignore,
+    Attack,
+    Flee,
+}
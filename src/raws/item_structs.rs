@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct Item {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    pub consumable: Option<Consumable>,
+    pub equippable: Option<Equippable>,
+    pub ranged_weapon: Option<RangedWeapon>,
+    /// In kilograms, for one of a stack. Weightless if missing.
+    pub weight: Option<f32>,
+    /// Carried in stacks, see `Stackable`.
+    pub stackable: Option<bool>,
+    /// Makes the item a stack of ammunition, this many strong.
+    pub ammunition: Option<i32>,
+    /// Opens a locked door.
+    pub key: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Renderable {
+    pub glyph: String,
+    pub fg: String,
+    pub bg: String,
+    pub order: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Consumable {
+    pub effects: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Equippable {
+    /// One of the `EquipmentSlot` labels.
+    pub slot: String,
+    /// Dice like `1d6+1`, for weapons.
+    pub damage: Option<String>,
+    pub hit_bonus: Option<i32>,
+    pub power_bonus: Option<i32>,
+    pub defense_bonus: Option<i32>,
+    pub max_hp_bonus: Option<i32>,
+    pub vision_bonus: Option<i32>,
+    pub speed_bonus: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RangedWeapon {
+    pub range: i32,
+    /// Dice like `1d6+1`.
+    pub damage: String,
+    pub hit_bonus: Option<i32>,
+    /// Name of the item it fires.
+    pub ammo: String,
+}
@@ -0,0 +1,77 @@
+use super::Renderable;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Mob {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    pub blocks_tile: bool,
+    pub stats: MobStats,
+    /// All 10 when absent.
+    pub attributes: Option<MobAttributes>,
+    /// No training when absent.
+    pub skills: Option<MobSkills>,
+    /// Damage dice of its natural attack, fists when absent.
+    pub attack: Option<String>,
+    pub vision_range: i32,
+    /// Energy gained per tick, normal speed when absent.
+    pub speed: Option<i32>,
+    /// Level 1 when absent.
+    pub level: Option<i32>,
+    /// Never flees and soon forgets when absent.
+    pub ai: Option<MobAi>,
+    /// One of the `faction_table` names, `Monsters` when absent.
+    pub faction: Option<String>,
+    /// Items it spawns with, equipped if they can be and carried otherwise.
+    pub equipment: Option<Vec<String>>,
+    /// Spells it casts, the first ones preferred.
+    pub spells: Option<Vec<MobSpell>>,
+}
+
+/// A spell, doing whichever of `damage`, `heal_ally` and `summon` it has.
+#[derive(Deserialize, Debug)]
+pub struct MobSpell {
+    pub name: String,
+    /// Turns to wait after casting it.
+    pub cooldown: i32,
+    /// Its vision range when absent.
+    pub range: Option<i32>,
+    /// Damage of a bolt at the player.
+    pub damage: Option<i32>,
+    /// Health given back to a hurt ally.
+    pub heal_ally: Option<i32>,
+    /// Kind of monster it calls.
+    pub summon: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobAi {
+    /// Percentage of its health below which it runs away.
+    pub flee_below: Option<i32>,
+    /// Turns it keeps searching for a player it lost sight of.
+    pub memory: Option<i32>,
+    /// Searches where the player is rather than where they were last seen.
+    pub relentless: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobStats {
+    pub max_hp: i32,
+    pub hp: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobAttributes {
+    pub might: i32,
+    pub fitness: i32,
+    pub quickness: i32,
+    pub intelligence: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobSkills {
+    pub melee: i32,
+    pub defense: i32,
+    #[serde(default)]
+    pub ranged: i32,
+}
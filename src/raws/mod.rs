@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+mod faction_structs;
+mod item_structs;
+mod mob_structs;
+mod prop_structs;
+mod rawmaster;
+mod spawn_table_structs;
+
+pub use faction_structs::Reaction;
+use faction_structs::*;
+use item_structs::*;
+use mob_structs::*;
+use prop_structs::*;
+pub use rawmaster::*;
+use spawn_table_structs::*;
+
+const RAW_FILE: &str = "resources/spawns.json";
+
+/// The raws the game was built with, for when there's no readable file on disk.
+const EMBEDDED_RAWS: &str = include_str!("../../resources/spawns.json");
+
+pub static RAWS: LazyLock<Mutex<RawMaster>> = LazyLock::new(|| Mutex::new(RawMaster::empty()));
+
+#[derive(Deserialize, Debug)]
+pub struct Raws {
+    pub items: Vec<Item>,
+    pub mobs: Vec<Mob>,
+    pub props: Vec<Prop>,
+    pub spawn_table: Vec<SpawnTableEntry>,
+    pub faction_table: Vec<FactionInfo>,
+}
+
+/// Reads the entity definitions from disk, so content can change without a rebuild.
+/// The file is looked for in the working directory, then next to the executable,
+/// and the raws built into the game are used when neither can be read.
+pub fn load_raws() {
+    let from_disk = raw_file_paths().into_iter().find_map(|path| {
+        let raw_string = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&raw_string) {
+            Ok(raws) => Some(raws),
+            Err(e) => {
+                rltk::console::log(format!(
+                    "WARNING - Unable to parse {}: {}",
+                    path.display(),
+                    e
+                ));
+                None
+            }
+        }
+    });
+    let decoder: Raws = from_disk.unwrap_or_else(|| {
+        serde_json::from_str(EMBEDDED_RAWS).expect("Unable to parse the built in raws")
+    });
+
+    RAWS.lock().unwrap().load(decoder);
+}
+
+fn raw_file_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(RAW_FILE)];
+    if let Some(dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        paths.push(dir.join(RAW_FILE));
+    }
+    paths
+}
+#[cfg(test)]
+mod tests {
+    use super::{spawn_named_entity, Raws, SpawnType, EMBEDDED_RAWS, RAWS};
+    use crate::{
+        components::Name,
+        headless::{
+            fixtures::{player, position_of, SEED},
+            HeadlessGame,
+        },
+    };
+    use specs::prelude::*;
+
+    #[test]
+    fn everything_in_the_spawn_table_spawns() {
+        let raws: Raws = serde_json::from_str(EMBEDDED_RAWS).unwrap();
+        assert!(!raws.spawn_table.is_empty());
+
+        let mut game = HeadlessGame::new(SEED);
+        let pos = position_of(&game, player(&game));
+        for spawn in raws.spawn_table.iter() {
+            let entity = spawn_named_entity(
+                &RAWS.lock().unwrap(),
+                game.world_mut().create_entity(),
+                &spawn.name,
+                SpawnType::AtPosition { x: pos.x, y: pos.y },
+            );
+            assert!(entity.is_some(), "{} doesn't spawn", spawn.name);
+            let names = game.world().read_storage::<Name>();
+            assert_eq!(names.get(entity.unwrap()).unwrap().name, spawn.name);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use super::Renderable;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Fixtures of the level, such as traps.
+#[derive(Deserialize, Debug)]
+pub struct Prop {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    /// Not drawn until spotted.
+    pub hidden: Option<bool>,
+    pub blocks_tile: Option<bool>,
+    pub blocks_visibility: Option<bool>,
+    /// Makes the prop a closed door.
+    pub door: Option<bool>,
+    pub entry_trigger: Option<EntryTrigger>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EntryTrigger {
+    pub effects: HashMap<String, String>,
+}
@@ -0,0 +1,572 @@
+use super::{MobSpell, Raws, Reaction};
+use crate::{components::*, initiative_system::NORMAL_SPEED, random_table::RandomTable};
+use specs::{
+    prelude::*,
+    saveload::{MarkedBuilder, SimpleMarker},
+};
+use std::collections::HashMap;
+
+/// Turns a monster keeps searching for the player, unless the raws say otherwise.
+const DEFAULT_MEMORY: i32 = 5;
+/// The faction of monsters the raws don't give one.
+const DEFAULT_FACTION: &str = "Monsters";
+
+#[derive(Clone, Copy)]
+pub enum SpawnType {
+    AtPosition {
+        x: i32,
+        y: i32,
+    },
+    /// In the backpack of `by`.
+    Carried {
+        by: Entity,
+    },
+    /// Equipped by `by`, in the item's slot.
+    Equipped {
+        by: Entity,
+    },
+}
+
+pub struct RawMaster {
+    raws: Raws,
+    item_index: HashMap<String, usize>,
+    mob_index: HashMap<String, usize>,
+    prop_index: HashMap<String, usize>,
+    faction_index: HashMap<String, HashMap<String, Reaction>>,
+}
+
+impl RawMaster {
+    pub fn empty() -> RawMaster {
+        RawMaster {
+            raws: Raws {
+                items: Vec::new(),
+                mobs: Vec::new(),
+                props: Vec::new(),
+                spawn_table: Vec::new(),
+                faction_table: Vec::new(),
+            },
+            item_index: HashMap::new(),
+            mob_index: HashMap::new(),
+            prop_index: HashMap::new(),
+            faction_index: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, raws: Raws) {
+        self.raws = raws;
+        self.item_index = HashMap::new();
+        self.mob_index = HashMap::new();
+        self.prop_index = HashMap::new();
+        self.faction_index = HashMap::new();
+
+        for (i, item) in self.raws.items.iter().enumerate() {
+            if self.item_index.insert(item.name.clone(), i).is_some() {
+                rltk::console::log(format!(
+                    "WARNING - duplicate item name in raws [{}]",
+                    item.name
+                ));
+            }
+        }
+        for (i, mob) in self.raws.mobs.iter().enumerate() {
+            if self.mob_index.insert(mob.name.clone(), i).is_some() {
+                rltk::console::log(format!(
+                    "WARNING - duplicate mob name in raws [{}]",
+                    mob.name
+                ));
+            }
+        }
+
+        for (i, prop) in self.raws.props.iter().enumerate() {
+            if self.prop_index.insert(prop.name.clone(), i).is_some() {
+                rltk::console::log(format!(
+                    "WARNING - duplicate prop name in raws [{}]",
+                    prop.name
+                ));
+            }
+        }
+
+        for mob in self.raws.mobs.iter() {
+            let equipment = mob.equipment.iter().flatten();
+            let summons = mob
+                .spells
+                .iter()
+                .flatten()
+                .filter_map(|s| s.summon.as_ref());
+            for item in equipment.filter(|item| !self.item_index.contains_key(*item)) {
+                rltk::console::log(format!(
+                    "WARNING - mob {} has unknown item [{}]",
+                    mob.name, item
+                ));
+            }
+            for summon in summons.filter(|summon| !self.mob_index.contains_key(*summon)) {
+                rltk::console::log(format!(
+                    "WARNING - mob {} summons unknown mob [{}]",
+                    mob.name, summon
+                ));
+            }
+        }
+
+        for faction in self.raws.faction_table.iter() {
+            self.faction_index
+                .insert(faction.name.clone(), faction.responses.clone());
+        }
+        for mob in self.raws.mobs.iter() {
+            let faction = mob.faction.as_deref().unwrap_or(DEFAULT_FACTION);
+            if !self.faction_index.contains_key(faction) {
+                rltk::console::log(format!(
+                    "WARNING - mob {} is in unknown faction [{}]",
+                    mob.name, faction
+                ));
+            }
+        }
+
+        for spawn in self.raws.spawn_table.iter() {
+            if !self.item_index.contains_key(&spawn.name)
+                && !self.mob_index.contains_key(&spawn.name)
+                && !self.prop_index.contains_key(&spawn.name)
+            {
+                rltk::console::log(format!(
+                    "WARNING - spawn table references unknown entity [{}]",
+                    spawn.name
+                ));
+            }
+        }
+    }
+}
+
+fn spawn_position(pos: SpawnType, new_entity: EntityBuilder) -> EntityBuilder {
+    match pos {
+        SpawnType::AtPosition { x, y } => new_entity.with(Position { x, y }),
+        SpawnType::Carried { by } => new_entity.with(InBackpack { owner: by }),
+        // the slot is only known to the item
+        SpawnType::Equipped { .. } => new_entity,
+    }
+}
+
+fn get_renderable_component(
+    renderable: &super::item_structs::Renderable,
+) -> crate::components::Renderable {
+    crate::components::Renderable {
+        glyph: rltk::to_cp437(renderable.glyph.chars().next().unwrap_or('?')),
+        fg: rltk::RGB::from_hex(&renderable.fg).expect("Invalid RGB"),
+        bg: rltk::RGB::from_hex(&renderable.bg).expect("Invalid RGB"),
+        render_order: renderable.order,
+    }
+}
+
+fn parse_effect_value(name: &str, effect: &str, value: &str) -> i32 {
+    value.parse::<i32>().unwrap_or_else(|_| {
+        rltk::console::log(format!(
+            "WARNING - effect {} of {} expects a number, got [{}]",
+            effect, name, value
+        ));
+        0
+    })
+}
+
+/// The status a consumable effect inflicts, its value being the duration.
+fn status_kind(effect: &str) -> Option<StatusKind> {
+    match effect {
+        "poison" => Some(StatusKind::Poison),
+        "burning" => Some(StatusKind::Burning),
+        "regeneration" => Some(StatusKind::Regeneration),
+        "haste" => Some(StatusKind::Haste),
+        "slow" => Some(StatusKind::Slow),
+        "blindness" => Some(StatusKind::Blindness),
+        "confusion" => Some(StatusKind::Confusion),
+        _ => None,
+    }
+}
+
+fn parse_dice(name: &str, dice: &str) -> rltk::DiceType {
+    rltk::parse_dice_string(dice).unwrap_or_else(|_| {
+        rltk::console::log(format!(
+            "WARNING - {} expects damage dice like 1d6, got [{}]",
+            name, dice
+        ));
+        rltk::DiceType::new(1, 4, 0)
+    })
+}
+
+fn parse_melee_weapon(name: &str, dice: &str, hit_bonus: i32) -> MeleeWeapon {
+    let dice = parse_dice(name, dice);
+    MeleeWeapon {
+        damage_n_dice: dice.n_dice,
+        damage_die_type: dice.die_type,
+        damage_bonus: dice.bonus,
+        hit_bonus,
+    }
+}
+
+pub fn spawn_named_item(
+    raws: &RawMaster,
+    new_entity: EntityBuilder,
+    key: &str,
+    pos: SpawnType,
+) -> Option<Entity> {
+    let item_template = &raws.raws.items[*raws.item_index.get(key)?];
+
+    let mut eb = spawn_position(pos, new_entity);
+
+    if let Some(renderable) = &item_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name {
+        name: item_template.name.clone(),
+    });
+    eb = eb.with(crate::components::Item {
+        weight: item_template.weight.unwrap_or(0.0),
+    });
+    if item_template.stackable.unwrap_or(false) {
+        eb = eb.with(Stackable { count: 1 });
+    }
+
+    if let Some(consumable) = &item_template.consumable {
+        eb = eb.with(crate::components::Consumable {});
+        let mut statuses = Vec::new();
+        for (effect_name, effect_value) in consumable.effects.iter() {
+            let name = &item_template.name;
+            match effect_name.as_str() {
+                "provides_healing" => {
+                    eb = eb.with(ProvidesHealing {
+                        heal_amount: parse_effect_value(name, effect_name, effect_value),
+                    })
+                }
+                "ranged" => {
+                    eb = eb.with(Ranged {
+                        range: parse_effect_value(name, effect_name, effect_value),
+                    })
+                }
+                "damage" => {
+                    eb = eb.with(InflictsDamage {
+                        damage: parse_effect_value(name, effect_name, effect_value),
+                    })
+                }
+                "area_of_effect" => {
+                    eb = eb.with(AreaOfEffect {
+                        radius: parse_effect_value(name, effect_name, effect_value),
+                    })
+                }
+                "teleports_symmetrically" => eb = eb.with(InflictsTeleportsSymetrically {}),
+                "food" => eb = eb.with(ProvidesFood {}),
+                _ => match status_kind(effect_name) {
+                    Some(kind) => {
+                        let turns = parse_effect_value(name, effect_name, effect_value);
+                        statuses.push(StatusEffect::new(kind, turns));
+                    }
+                    None => {
+                        rltk::console::log(format!(
+                            "WARNING - consumable effect {} not implemented ({})",
+                            effect_name, name
+                        ));
+                    }
+                },
+            }
+        }
+        if !statuses.is_empty() {
+            statuses.sort_by_key(|status| status.kind as usize);
+            eb = eb.with(InflictsStatus { effects: statuses });
+        }
+    }
+
+    if let Some(equippable) = &item_template.equippable {
+        let slot = EquipmentSlot::ALL
+            .into_iter()
+            .find(|slot| slot.label() == equippable.slot)
+            .unwrap_or_else(|| {
+                rltk::console::log(format!(
+                    "WARNING - unknown equipment slot {} of {}",
+                    equippable.slot, item_template.name
+                ));
+                EquipmentSlot::Melee
+            });
+        eb = eb.with(crate::components::Equippable { slot });
+        if let SpawnType::Equipped { by } = pos {
+            eb = eb.with(Equipped { owner: by, slot });
+        }
+        if let Some(damage) = &equippable.damage {
+            let hit_bonus = equippable.hit_bonus.unwrap_or(0);
+            eb = eb.with(parse_melee_weapon(&item_template.name, damage, hit_bonus));
+        }
+        eb = eb.with(EquipmentBonus {
+            power: equippable.power_bonus.unwrap_or(0),
+            defense: equippable.defense_bonus.unwrap_or(0),
+            max_hp: equippable.max_hp_bonus.unwrap_or(0),
+            vision: equippable.vision_bonus.unwrap_or(0),
+            speed: equippable.speed_bonus.unwrap_or(0),
+        });
+    }
+
+    if let Some(ranged_weapon) = &item_template.ranged_weapon {
+        let hit_bonus = ranged_weapon.hit_bonus.unwrap_or(0);
+        let dice = parse_dice(&item_template.name, &ranged_weapon.damage);
+        eb = eb.with(crate::components::RangedWeapon {
+            range: ranged_weapon.range,
+            damage_n_dice: dice.n_dice,
+            damage_die_type: dice.die_type,
+            damage_bonus: dice.bonus,
+            hit_bonus,
+            ammo: ranged_weapon.ammo.clone(),
+        });
+    }
+
+    if let Some(count) = item_template.ammunition {
+        eb = eb.with(Ammunition { count });
+    }
+
+    if item_template.key.unwrap_or(false) {
+        eb = eb.with(Key {});
+    }
+
+    Some(eb.marked::<SimpleMarker<SerializeMe>>().build())
+}
+
+pub fn spawn_named_mob(
+    raws: &RawMaster,
+    new_entity: EntityBuilder,
+    key: &str,
+    pos: SpawnType,
+) -> Option<Entity> {
+    let mob_template = &raws.raws.mobs[*raws.mob_index.get(key)?];
+
+    let mut eb = spawn_position(pos, new_entity);
+
+    if let Some(renderable) = &mob_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name {
+        name: mob_template.name.clone(),
+    });
+    eb = eb.with(Monster {});
+    if mob_template.blocks_tile {
+        eb = eb.with(BlocksTile {});
+    }
+    eb = eb.with(CombatStats {
+        max_hp: mob_template.stats.max_hp,
+        hp: mob_template.stats.hp,
+    });
+    eb = eb.with(match &mob_template.attributes {
+        Some(attributes) => Attributes {
+            might: attributes.might,
+            fitness: attributes.fitness,
+            quickness: attributes.quickness,
+            intelligence: attributes.intelligence,
+        },
+        None => Attributes {
+            might: 10,
+            fitness: 10,
+            quickness: 10,
+            intelligence: 10,
+        },
+    });
+    eb = eb.with(match &mob_template.skills {
+        Some(skills) => Skills {
+            melee: skills.melee,
+            defense: skills.defense,
+            ranged: skills.ranged,
+        },
+        None => Skills {
+            melee: 0,
+            defense: 0,
+            ranged: 0,
+        },
+    });
+    if let Some(attack) = &mob_template.attack {
+        eb = eb.with(parse_melee_weapon(&mob_template.name, attack, 0));
+    }
+    eb = eb.with(Viewshed {
+        visible_tiles: Vec::new(),
+        range: mob_template.vision_range,
+        dirty: true,
+    });
+    eb = eb.with(Energy {
+        speed: mob_template.speed.unwrap_or(NORMAL_SPEED),
+        current: 0,
+    });
+    eb = eb.with(Experience {
+        level: mob_template.level.unwrap_or(1),
+        xp: 0,
+    });
+    eb = eb.with(Faction {
+        name: mob_template
+            .faction
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FACTION.to_string()),
+    });
+    let ai = mob_template.ai.as_ref();
+    eb = eb.with(Brain {
+        state: AiState::Idle,
+        flee_below: ai.and_then(|ai| ai.flee_below).unwrap_or(0),
+        memory: ai.and_then(|ai| ai.memory).unwrap_or(DEFAULT_MEMORY),
+        relentless: ai.and_then(|ai| ai.relentless).unwrap_or(false),
+    });
+    if let Some(spells) = &mob_template.spells {
+        eb = eb.with(SpellBook {
+            spells: spells
+                .iter()
+                .filter_map(|spell| {
+                    parse_spell(&mob_template.name, spell, mob_template.vision_range)
+                })
+                .collect(),
+        });
+    }
+
+    let world = eb.world;
+    let mob = eb.marked::<SimpleMarker<SerializeMe>>().build();
+    for item in mob_template.equipment.iter().flatten() {
+        let Some(item_idx) = raws.item_index.get(item) else {
+            continue;
+        };
+        let pos = if raws.raws.items[*item_idx].equippable.is_some() {
+            SpawnType::Equipped { by: mob }
+        } else {
+            SpawnType::Carried { by: mob }
+        };
+        spawn_named_item(raws, world.create_entity_unchecked(), item, pos);
+    }
+
+    Some(mob)
+}
+
+fn parse_spell(name: &str, spell: &MobSpell, vision_range: i32) -> Option<Spell> {
+    let effect = if let Some(damage) = spell.damage {
+        SpellEffect::Bolt { damage }
+    } else if let Some(amount) = spell.heal_ally {
+        SpellEffect::HealAlly { amount }
+    } else if let Some(mob) = &spell.summon {
+        SpellEffect::Summon { mob: mob.clone() }
+    } else {
+        rltk::console::log(format!(
+            "WARNING - spell {} of {} does nothing",
+            spell.name, name
+        ));
+        return None;
+    };
+    Some(Spell {
+        name: spell.name.clone(),
+        effect,
+        range: spell.range.unwrap_or(vision_range),
+        cooldown: spell.cooldown,
+        ready_in: 0,
+    })
+}
+
+pub fn spawn_named_prop(
+    raws: &RawMaster,
+    new_entity: EntityBuilder,
+    key: &str,
+    pos: SpawnType,
+) -> Option<Entity> {
+    let prop_template = &raws.raws.props[*raws.prop_index.get(key)?];
+
+    let mut eb = spawn_position(pos, new_entity);
+
+    if let Some(renderable) = &prop_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name {
+        name: prop_template.name.clone(),
+    });
+    if prop_template.hidden.unwrap_or(false) {
+        eb = eb.with(Hidden {});
+    }
+    if prop_template.blocks_tile.unwrap_or(false) {
+        eb = eb.with(BlocksTile {});
+    }
+    if prop_template.blocks_visibility.unwrap_or(false) {
+        eb = eb.with(BlocksVisibility {});
+    }
+    if prop_template.door.unwrap_or(false) {
+        eb = eb.with(Door {
+            open: false,
+            locked: false,
+        });
+    }
+
+    if let Some(entry_trigger) = &prop_template.entry_trigger {
+        eb = eb.with(EntryTrigger {});
+        let mut statuses = Vec::new();
+        for (effect_name, effect_value) in entry_trigger.effects.iter() {
+            let name = &prop_template.name;
+            match effect_name.as_str() {
+                "damage" => {
+                    eb = eb.with(InflictsDamage {
+                        damage: parse_effect_value(name, effect_name, effect_value),
+                    })
+                }
+                "teleport" => eb = eb.with(TeleportsRandomly {}),
+                "single_activation" => eb = eb.with(SingleActivation {}),
+                _ => match status_kind(effect_name) {
+                    Some(kind) => {
+                        let turns = parse_effect_value(name, effect_name, effect_value);
+                        statuses.push(StatusEffect::new(kind, turns));
+                    }
+                    None => {
+                        rltk::console::log(format!(
+                            "WARNING - trigger effect {} not implemented ({})",
+                            effect_name, name
+                        ));
+                    }
+                },
+            }
+        }
+        if !statuses.is_empty() {
+            statuses.sort_by_key(|status| status.kind as usize);
+            eb = eb.with(InflictsStatus { effects: statuses });
+        }
+    }
+
+    Some(eb.marked::<SimpleMarker<SerializeMe>>().build())
+}
+
+/// Spawns whatever the raws call `key`, `None` if nothing has that name.
+pub fn spawn_named_entity(
+    raws: &RawMaster,
+    new_entity: EntityBuilder,
+    key: &str,
+    pos: SpawnType,
+) -> Option<Entity> {
+    if raws.item_index.contains_key(key) {
+        return spawn_named_item(raws, new_entity, key, pos);
+    } else if raws.mob_index.contains_key(key) {
+        return spawn_named_mob(raws, new_entity, key, pos);
+    } else if raws.prop_index.contains_key(key) {
+        return spawn_named_prop(raws, new_entity, key, pos);
+    }
+
+    None
+}
+
+pub fn get_spawn_table_for_depth(raws: &RawMaster, depth: i32) -> RandomTable {
+    let mut rt = RandomTable::new();
+    for spawn in raws
+        .raws
+        .spawn_table
+        .iter()
+        .filter(|spawn| depth >= spawn.min_depth && depth <= spawn.max_depth)
+    {
+        let mut weight = spawn.weight;
+        if spawn.add_map_depth_to_weight.unwrap_or(false) {
+            weight += depth;
+        }
+        rt = rt.add(spawn.name.clone(), weight);
+    }
+
+    rt
+}
+
+/// How members of `my_faction` treat those of `their_faction`. Factions the
+/// raws don't know ignore everyone.
+pub fn faction_reaction(my_faction: &str, their_faction: &str, raws: &RawMaster) -> Reaction {
+    let Some(responses) = raws.faction_index.get(my_faction) else {
+        return Reaction::Ignore;
+    };
+    responses
+        .get(their_faction)
+        .or_else(|| responses.get("Default"))
+        .copied()
+        .unwrap_or(Reaction::Ignore)
+}
@@ -4,9 +4,14 @@ use specs::prelude::*;
 use specs::saveload::{
     DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::path::Path;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 macro_rules! serialize_individually {
     ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
@@ -16,81 +21,302 @@ macro_rules! serialize_individually {
             &$data.0,
             &$data.1,
             &mut $ser,
-        )
-        .unwrap();
+        )?;
         )*
     };
 }
 
 macro_rules! deserialize_individually {
-    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+    ($ecs:expr, $values:expr, $data:expr, $( $type:ty),*) => {
         $(
         DeserializeComponents::<NoError, _>::deserialize(
             &mut ( &mut $ecs.write_storage::<$type>(), ),
             &mut $data.0, // entities
             &mut $data.1, // marker
             &mut $data.2, // allocater
-            &mut $de,
+            $values.next().ok_or_else(|| SaveError::Corrupted("missing components".to_string()))?,
         )
-        .unwrap();
+        .map_err(|e| SaveError::Corrupted(e.to_string()))?;
         )*
     };
 }
 
-pub fn save_game(ecs: &mut World) {
+pub const SAVE_SLOTS: usize = 5;
+
+/// Bump this whenever the saved data changes (a component added to the lists
+/// below, a field renamed...), and teach `migrate` how to upgrade older saves.
+const SAVE_FORMAT_VERSION: u32 = 1;
+
+/// Where the save slots are written. A resource so tests can use their own.
+pub struct SaveDirectory {
+    pub path: PathBuf,
+}
+
+/// The headers of the save slots, so menus don't read the files every frame.
+/// Refreshed when the slot browser opens and whenever a save is written or deleted.
+#[derive(Default)]
+pub struct SaveSlots {
+    pub headers: Vec<Option<Result<SaveHeader, SaveError>>>,
+}
+
+/// Written before the world, so a save can be checked before touching the ECS.
+#[derive(Serialize, Deserialize)]
+pub struct SaveHeader {
+    pub format_version: u32,
+    pub game_version: String,
+    pub metadata: Option<SaveMetadata>,
+}
+
+/// What the slot browser shows about a save.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveMetadata {
+    pub depth: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub turns: i32,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    pub seed: u64,
+    pub permadeath: bool,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Corrupted(String),
+    Incompatible { format_version: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "Unable to access save: {}", e),
+            SaveError::Corrupted(e) => write!(f, "Save is corrupted: {}", e),
+            SaveError::Incompatible { format_version } => write!(
+                f,
+                "Save is incompatible (format {}, expected {})",
+                format_version, SAVE_FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> SaveError {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> SaveError {
+        SaveError::Corrupted(e.to_string())
+    }
+}
+
+/// Upgrades the serialized storages of an older save to the current format.
+/// No format has been retired yet, older saves (before headers) can't be read.
+fn migrate(header: &SaveHeader, values: Vec<Value>) -> Result<Vec<Value>, SaveError> {
+    match header.format_version {
+        SAVE_FORMAT_VERSION => Ok(values),
+        format_version => Err(SaveError::Incompatible { format_version }),
+    }
+}
+
+fn slot_path(ecs: &World, slot: usize) -> PathBuf {
+    ecs.fetch::<SaveDirectory>()
+        .path
+        .join(format!("slot{}.json", slot + 1))
+}
+
+fn read_header(reader: impl std::io::Read) -> Result<SaveHeader, SaveError> {
+    let mut stream = serde_json::Deserializer::from_reader(reader).into_iter::<Value>();
+    let value = match stream.next() {
+        Some(value) => value?,
+        None => return Err(SaveError::Corrupted("empty file".to_string())),
+    };
+    // saves from before headers start straight with the storages
+    if value.is_array() {
+        return Err(SaveError::Incompatible { format_version: 0 });
+    }
+    // another format may lay its header out differently, but keeps the version
+    let format_version = value
+        .get("format_version")
+        .and_then(Value::as_u64)
+        .and_then(|version| u32::try_from(version).ok());
+    serde_json::from_value(value).map_err(|e| match format_version {
+        Some(format_version) if format_version != SAVE_FORMAT_VERSION => {
+            SaveError::Incompatible { format_version }
+        }
+        _ => e.into(),
+    })
+}
+
+/// The header of the save in a slot, `None` if the slot is empty.
+pub fn read_slot(ecs: &World, slot: usize) -> Option<Result<SaveHeader, SaveError>> {
+    let path = slot_path(ecs, slot);
+    if !path.exists() {
+        return None;
+    }
+    Some(
+        File::open(path)
+            .map_err(SaveError::from)
+            .and_then(|file| read_header(BufReader::new(file))),
+    )
+}
+
+pub fn delete_save(ecs: &World, slot: usize) -> Result<(), SaveError> {
+    let result = fs::remove_file(slot_path(ecs, slot));
+    refresh_save_slots(ecs);
+    result?;
+    Ok(())
+}
+
+/// Reads the headers of every slot again into `SaveSlots`.
+pub fn refresh_save_slots(ecs: &World) {
+    let headers = (0..SAVE_SLOTS).map(|slot| read_slot(ecs, slot)).collect();
+    ecs.write_resource::<SaveSlots>().headers = headers;
+}
+
+fn current_metadata(ecs: &World) -> SaveMetadata {
+    let player = ecs.fetch::<Entity>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let (hp, max_hp) = combat_stats
+        .get(*player)
+        .map_or((0, 0), |stats| (stats.hp, stats.max_hp));
+
+    SaveMetadata {
+        depth: ecs.fetch::<super::map::Map>().depth,
+        hp,
+        max_hp,
+        turns: ecs.fetch::<super::TurnCounter>().turns,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs()),
+        seed: ecs.fetch::<super::GameSeed>().seed,
+        permadeath: ecs.fetch::<super::GameMode>().permadeath,
+    }
+}
+
+pub fn save_game(ecs: &mut World, slot: usize) -> Result<(), SaveError> {
     let map_copy = ecs.get_mut::<super::map::Map>().unwrap().clone();
+    let seed = ecs.fetch::<super::GameSeed>().seed;
+    let turns = ecs.fetch::<super::TurnCounter>().turns;
+    let permadeath = ecs.fetch::<super::GameMode>().permadeath;
+    let dungeon = ecs
+        .get_mut::<super::dungeon::MasterDungeonMap>()
+        .unwrap()
+        .clone();
     let save_helper = ecs
         .create_entity()
-        .with(SerializationHelper { map: map_copy })
+        .with(SerializationHelper {
+            map: map_copy,
+            seed,
+            turns,
+            permadeath,
+            dungeon,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
-    {
-        let data = (
-            ecs.entities(),
-            ecs.read_storage::<SimpleMarker<SerializeMe>>(),
-        );
-        let writer = File::create("./savegame.json").unwrap();
-        let mut serializer = serde_json::Serializer::new(writer);
-        serialize_individually!(
-            ecs,
-            serializer,
-            data,
-            Position,
-            Renderable,
-            Player,
-            Viewshed,
-            Monster,
-            Name,
-            BlocksTile,
-            CombatStats,
-            SufferDamage,
-            WantsToMelee,
-            Item,
-            Consumable,
-            Ranged,
-            InflictsDamage,
-            AreaOfEffect,
-            Confusion,
-            ProvidesHealing,
-            InBackpack,
-            WantsToPickupItem,
-            WantsToUseItem,
-            WantsToDropItem,
-            SerializationHelper,
-            Equippable,
-            Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
-            WantsToRemoveItem,
-            ParticleLifetime
-        );
+    let result = write_save(ecs, slot);
+    ecs.delete_entity(save_helper).expect("Crash on cleanup");
+    refresh_save_slots(ecs);
+    if result.is_ok() {
+        ecs.write_resource::<super::GameMode>().slot = Some(slot);
     }
+    result
+}
 
-    ecs.delete_entity(save_helper).expect("Crash on cleanup");
+fn write_save(ecs: &mut World, slot: usize) -> Result<(), SaveError> {
+    let metadata = current_metadata(ecs);
+    let path = slot_path(ecs, slot);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let data = (
+        ecs.entities(),
+        ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+    );
+    let writer = File::create(path)?;
+    let mut serializer = serde_json::Serializer::new(writer);
+    SaveHeader {
+        format_version: SAVE_FORMAT_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        metadata: Some(metadata),
+    }
+    .serialize(&mut serializer)?;
+    serialize_individually!(
+        ecs,
+        serializer,
+        data,
+        Position,
+        Renderable,
+        Player,
+        Viewshed,
+        Monster,
+        Name,
+        BlocksTile,
+        CombatStats,
+        WantsToMelee,
+        Item,
+        Consumable,
+        Ranged,
+        InflictsDamage,
+        AreaOfEffect,
+        InflictsStatus,
+        ProvidesHealing,
+        InBackpack,
+        WantsToPickupItem,
+        WantsToUseItem,
+        WantsToDropItem,
+        SerializationHelper,
+        Equippable,
+        Equipped,
+        MeleeWeapon,
+        EquipmentBonus,
+        WantsToRemoveItem,
+        ParticleLifetime,
+        OtherLevelPosition,
+        Energy,
+        HungerClock,
+        ProvidesFood,
+        Experience,
+        Attributes,
+        Skills,
+        RangedWeapon,
+        Ammunition,
+        StatusEffects,
+        TeleportsRandomly,
+        Hidden,
+        EntryTrigger,
+        SingleActivation,
+        Door,
+        BlocksVisibility,
+        Key,
+        Brain,
+        SpellBook,
+        Faction,
+        Stackable,
+        InflictsTeleportsSymetrically
+    );
+
+    Ok(())
 }
 
-pub fn load_game(ecs: &mut World) {
+/// Replaces the world with the saved one. The header and the shape of the
+/// file are checked before anything is deleted, a failure past that point
+/// leaves a broken world that the caller has to rebuild.
+/// A permadeath save is deleted once loaded.
+pub fn load_game(ecs: &mut World, slot: usize) -> Result<(), SaveError> {
+    let data = fs::read_to_string(slot_path(ecs, slot))?;
+    let header = read_header(data.as_bytes())?;
+    let values = serde_json::Deserializer::from_str(&data)
+        .into_iter::<Value>()
+        .skip(1)
+        .collect::<Result<Vec<Value>, _>>()?;
+    let mut values = migrate(&header, values)?.into_iter();
+
     {
         // delete all
         let mut to_delete = Vec::new();
@@ -102,9 +328,6 @@ pub fn load_game(ecs: &mut World) {
         }
     }
 
-    let data = fs::read_to_string("./savegame.json").unwrap();
-    let mut de = serde_json::Deserializer::from_str(&data);
-
     {
         let mut d = (
             &mut ecs.entities(),
@@ -113,7 +336,7 @@ pub fn load_game(ecs: &mut World) {
         );
         deserialize_individually!(
             ecs,
-            de,
+            values,
             d,
             Position,
             Renderable,
@@ -123,14 +346,13 @@ pub fn load_game(ecs: &mut World) {
             Name,
             BlocksTile,
             CombatStats,
-            SufferDamage,
             WantsToMelee,
             Item,
             Consumable,
             Ranged,
             InflictsDamage,
             AreaOfEffect,
-            Confusion,
+            InflictsStatus,
             ProvidesHealing,
             InBackpack,
             WantsToPickupItem,
@@ -139,10 +361,32 @@ pub fn load_game(ecs: &mut World) {
             SerializationHelper,
             Equippable,
             Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
+            MeleeWeapon,
+            EquipmentBonus,
             WantsToRemoveItem,
-            ParticleLifetime
+            ParticleLifetime,
+            OtherLevelPosition,
+            Energy,
+            HungerClock,
+            ProvidesFood,
+            Experience,
+            Attributes,
+            Skills,
+            RangedWeapon,
+            Ammunition,
+            StatusEffects,
+            TeleportsRandomly,
+            Hidden,
+            EntryTrigger,
+            SingleActivation,
+            Door,
+            BlocksVisibility,
+            Key,
+            Brain,
+            SpellBook,
+            Faction,
+            Stackable,
+            InflictsTeleportsSymetrically
         );
     }
 
@@ -157,6 +401,20 @@ pub fn load_game(ecs: &mut World) {
             let mut world_map = ecs.write_resource::<super::map::Map>();
             *world_map = h.map.clone();
             world_map.tile_content = vec![Vec::new(); super::map::MAPCOUNT];
+            let mut game_seed = ecs.write_resource::<super::GameSeed>();
+            game_seed.seed = h.seed;
+            let mut turn_counter = ecs.write_resource::<super::TurnCounter>();
+            turn_counter.turns = h.turns;
+            // rolls after a load are as repeatable as the levels
+            let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+            *rng = rltk::RandomNumberGenerator::seeded(
+                super::level_seed(h.seed, h.map.depth) ^ h.turns as u64,
+            );
+            let mut game_mode = ecs.write_resource::<super::GameMode>();
+            game_mode.permadeath = h.permadeath;
+            game_mode.slot = Some(slot);
+            let mut dungeon = ecs.write_resource::<super::dungeon::MasterDungeonMap>();
+            *dungeon = h.dungeon.clone();
             delete_me = Some(e);
         }
 
@@ -167,9 +425,156 @@ pub fn load_game(ecs: &mut World) {
             *player_resource = e;
         }
     }
-    ecs.delete_entity(delete_me.unwrap()).expect("Unable to delete helper");
+    let helper = delete_me.ok_or_else(|| SaveError::Corrupted("no map in save".to_string()))?;
+    ecs.delete_entity(helper).expect("Unable to delete helper");
+    // what blocks movement and sight isn't saved with the map
+    super::map_indexing_system::MapIndexingSystem {}.run_now(ecs);
+
+    if ecs.fetch::<super::GameMode>().permadeath {
+        delete_save(ecs, slot)?;
+    }
+
+    Ok(())
+}
+
+/// Called when the player dies: a permadeath run doesn't leave a save behind.
+pub fn delete_permadeath_save(ecs: &World) {
+    let slot = {
+        let mut game_mode = ecs.write_resource::<super::GameMode>();
+        if !game_mode.permadeath {
+            return;
+        }
+        game_mode.slot.take()
+    };
+    if let Some(slot) = slot {
+        if slot_path(ecs, slot).exists() {
+            if let Err(e) = delete_save(ecs, slot) {
+                rltk::console::log(format!("WARNING - {}", e));
+            }
+        }
+    }
+}
+
+pub fn does_save_exist(ecs: &World) -> bool {
+    ecs.fetch::<SaveSlots>().headers.iter().any(Option::is_some)
 }
+#[cfg(test)]
+mod tests {
+    use super::{delete_save, does_save_exist, load_game, read_slot, save_game, SaveError};
+    use crate::{
+        components::Monster,
+        headless::{
+            fixtures::{use_save_directory, SEED},
+            Action, HeadlessGame,
+        },
+    };
+    use rltk::RandomNumberGenerator;
+    use serde_json::Value;
+    use specs::prelude::*;
+    use std::fs;
+
+    #[test]
+    fn loading_a_save_replays_the_same_rolls() {
+        let mut game = HeadlessGame::new(SEED);
+        let save_dir = use_save_directory(&mut game, "same_rolls");
+        game.act(Action::Wait);
+        save_game(game.world_mut(), 0).unwrap();
+
+        let mut rolls = Vec::new();
+        for _ in 0..2 {
+            load_game(game.world_mut(), 0).unwrap();
+            let mut rng = game.world().write_resource::<RandomNumberGenerator>();
+            rolls.push(rng.next_u64());
+        }
+        assert_eq!(rolls[0], rolls[1]);
+        fs::remove_dir_all(save_dir).unwrap();
+    }
+
+    #[test]
+    fn slots_are_independent_and_can_be_deleted() {
+        let mut game = HeadlessGame::new(SEED);
+        let save_dir = use_save_directory(&mut game, "slots");
+        assert!(!does_save_exist(game.world()));
+
+        save_game(game.world_mut(), 2).unwrap();
+        assert!(does_save_exist(game.world()));
+        assert!(read_slot(game.world(), 0).is_none());
+        assert!(read_slot(game.world(), 2).is_some());
 
-pub fn does_save_exist() -> bool {
-    Path::new("./savegame.json").exists()
+        delete_save(game.world(), 2).unwrap();
+        assert!(read_slot(game.world(), 2).is_none());
+        assert!(!does_save_exist(game.world()));
+        fs::remove_dir_all(save_dir).unwrap();
+    }
+
+    #[test]
+    fn saves_of_another_format_are_incompatible() {
+        let mut game = HeadlessGame::new(SEED);
+        let save_dir = use_save_directory(&mut game, "other_format");
+        save_game(game.world_mut(), 0).unwrap();
+        let monster_count = game.world().read_storage::<Monster>().join().count();
+
+        let path = save_dir.join("slot1.json");
+        let data = fs::read_to_string(&path).unwrap();
+        let mut values: Vec<Value> = serde_json::Deserializer::from_str(&data)
+            .into_iter::<Value>()
+            .map(|value| value.unwrap())
+            .collect();
+        values[0]["format_version"] = Value::from(1000);
+        let other_save: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        fs::write(&path, other_save.join("")).unwrap();
+
+        let result = load_game(game.world_mut(), 0);
+        assert!(matches!(
+            result,
+            Err(SaveError::Incompatible {
+                format_version: 1000
+            })
+        ));
+        assert_eq!(
+            game.world().read_storage::<Monster>().join().count(),
+            monster_count
+        );
+        fs::remove_dir_all(save_dir).unwrap();
+    }
+
+    #[test]
+    fn bad_saves_are_reported_not_loaded() {
+        let mut game = HeadlessGame::new(SEED);
+        let save_dir = use_save_directory(&mut game, "bad_saves");
+        fs::create_dir_all(&save_dir).unwrap();
+        let monster_count = game.world().read_storage::<Monster>().join().count();
+
+        // written before save headers existed
+        fs::write(save_dir.join("slot1.json"), "[]").unwrap();
+        let result = load_game(game.world_mut(), 0);
+        assert!(matches!(
+            result,
+            Err(SaveError::Incompatible { format_version: 0 })
+        ));
+
+        fs::write(save_dir.join("slot1.json"), "{ not json").unwrap();
+        let result = load_game(game.world_mut(), 0);
+        assert!(matches!(result, Err(SaveError::Corrupted(_))));
+
+        // a header cut short is corrupted, not from another version
+        fs::write(save_dir.join("slot1.json"), r#"{ "format_version": 1 }"#).unwrap();
+        let result = load_game(game.world_mut(), 0);
+        assert!(matches!(result, Err(SaveError::Corrupted(_))));
+
+        // a later version's header tells its version even if it can't be read
+        fs::write(save_dir.join("slot1.json"), r#"{ "format_version": 7 }"#).unwrap();
+        let result = load_game(game.world_mut(), 0);
+        assert!(matches!(
+            result,
+            Err(SaveError::Incompatible { format_version: 7 })
+        ));
+
+        // nothing was deleted
+        assert_eq!(
+            game.world().read_storage::<Monster>().join().count(),
+            monster_count
+        );
+        fs::remove_dir_all(save_dir).unwrap();
+    }
 }
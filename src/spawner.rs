@@ -1,16 +1,16 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::{
-    components::{
-        AreaOfEffect, Confusion, Consumable, InflictsDamage, Item, ProvidesHealing, Ranged,
-        SerializeMe, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus, TeleportsSymetrically, InflictsTeleportsSymetrically,
-    },
-    map::MAPWIDTH,
-    random_table::RandomTable,
-    rect::Rect,
+    components::{Door, SerializeMe},
+    map::{Map, TileType, MAPWIDTH},
+    raws::{get_spawn_table_for_depth, spawn_named_entity, SpawnType, RAWS},
 };
 
-use super::{BlocksTile, CombatStats, Monster, Name, Player, Position, Renderable, Viewshed};
+use super::{
+    hunger_system::WELL_FED_TURNS, initiative_system::NORMAL_SPEED, Attributes, CombatStats,
+    Energy, Experience, Faction, HungerClock, HungerState, Name, Player, Position, Renderable,
+    Skills, Viewshed,
+};
 use rltk::{RandomNumberGenerator, RGB};
 use specs::{
     prelude::*,
@@ -18,6 +18,8 @@ use specs::{
 };
 
 const MAX_MONSTERS: i32 = 4;
+/// One door in this many is locked, when that leaves the stairs down reachable.
+const LOCKED_DOOR_ONE_IN: i32 = 6;
 
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
     ecs.create_entity()
@@ -40,285 +42,159 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
         .with(Name {
             name: "Player".to_string(),
         })
+        .with(Faction {
+            name: "Player".to_string(),
+        })
         .with(CombatStats {
             max_hp: 180,
             hp: 180,
-            defense: 2,
-            power: 5,
         })
+        .with(Attributes {
+            might: 12,
+            fitness: 12,
+            quickness: 12,
+            intelligence: 10,
+        })
+        .with(Skills {
+            melee: 1,
+            defense: 1,
+            ranged: 1,
+        })
+        .with(Energy {
+            speed: NORMAL_SPEED,
+            current: 0,
+        })
+        .with(HungerClock {
+            state: HungerState::WellFed,
+            duration: WELL_FED_TURNS,
+        })
+        .with(Experience { level: 1, xp: 0 })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-pub fn spawn_room_content(ecs: &mut World, room: &Rect, map_depth: i32) {
-    let spawn_table = room_table(map_depth);
-    let mut spawn_points: HashMap<usize, String> = HashMap::new();
+/// Fills one spawn region of the map, as handed out by the map builder.
+pub fn spawn_region(ecs: &mut World, area: &[usize], map_depth: i32) {
+    let spawn_table = get_spawn_table_for_depth(&RAWS.lock().unwrap(), map_depth);
+    // ordered so that entities are always created in the same order for a given seed
+    let mut spawn_points: BTreeMap<usize, String> = BTreeMap::new();
+    let mut areas: Vec<usize> = Vec::from(area);
 
     {
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_spawns = rng.roll_dice(1, MAX_MONSTERS + 3) + (map_depth - 1) - 3;
+        let num_spawns = i32::min(
+            areas.len() as i32,
+            rng.roll_dice(1, MAX_MONSTERS + 3) + (map_depth - 1) - 3,
+        );
 
         for _i in 0..num_spawns {
-            let mut added = false;
-            let mut tries = 0;
-            while !added && tries < 20 {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAPWIDTH) + x; // should be a helper function for Map...
-                if !spawn_points.contains_key(&idx) {
-                    spawn_points.insert(idx, spawn_table.roll(&mut rng));
-                    added = true;
-                } else {
-                    tries += 1;
-                }
-            }
+            let array_index = (rng.roll_dice(1, areas.len() as i32) - 1) as usize;
+            let map_idx = areas[array_index];
+            spawn_points.insert(map_idx, spawn_table.roll(&mut rng));
+            areas.remove(array_index);
         }
     }
 
+    let raws = RAWS.lock().unwrap();
     for spawn in spawn_points.iter() {
         let x = (*spawn.0 % MAPWIDTH) as i32;
         let y = (*spawn.0 / MAPWIDTH) as i32;
 
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(ecs, x, y),
-            "Orc" => orc(ecs, x, y),
-            "Health Potion" => health_potion(ecs, x, y),
-            "Fireball Scroll" => fireball_scroll(ecs, x, y),
-            "Confusion Scroll" => confusion_scroll(ecs, x, y),
-            "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
-            "Dagger" => dagger(ecs, x, y),
-            "Shield" => shield(ecs, x, y),
-            "Longsword" => longsword(ecs, x, y),
-            "Tower Shield" => tower_shield(ecs, x, y),
-            "Offensive Teleport scroll" => offensive_teleport_scroll(ecs, x, y),
-            _ => {}
+        let spawn_result = spawn_named_entity(
+            &raws,
+            ecs.create_entity(),
+            spawn.1,
+            SpawnType::AtPosition { x, y },
+        );
+        if spawn_result.is_none() {
+            rltk::console::log(format!("WARNING - don't know how to spawn [{}]", spawn.1));
         }
     }
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('¡'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Health Potion".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(ProvidesHealing { heal_amount: 8 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Magic Missile Scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::ORANGE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Fireball Scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 20 })
-        .with(AreaOfEffect { radius: 2 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::AZURE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Confusion scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(Confusion { turns: 4 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn offensive_teleport_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('t'),
-            fg: RGB::named(rltk::YELLOWGREEN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Offensive Teleport scroll".to_string(),
-        })
-        .with(Item {})
-        .with(Consumable {})
-        .with(Ranged { range: 6 })
-        .with(InflictsTeleportsSymetrically{})
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('o'), "Orc");
-}
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('g'), "Goblin");
-}
-
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1,
-        })
-        .with(Viewshed {
-            visible_tiles: Vec::new(),
-            range: 8,
-            dirty: true,
-        })
-        .with(Monster {})
-        .with(Name {
-            name: name.to_string(),
-        })
-        .with(BlocksTile {})
-        .with(CombatStats {
-            max_hp: 16,
-            hp: 16,
-            defense: 1,
-            power: 4,
-        })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-fn room_table(map_depth: i32) -> RandomTable {
-    RandomTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + map_depth)
-        .add("Health Potion", 7)
-        .add("Fireball Scroll", 2 + map_depth)
-        .add("Confusion Scroll", 12 + map_depth)
-        .add("Magic Missile Scroll", 4)
-        .add("Dagger", 3)
-        .add("Shield", 3)
-        .add("Longsword", map_depth - 1)
-        .add("Tower Shield", map_depth - 1)
-        .add("Offensive Teleport scroll", 1)
-}
-
-fn dagger(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Dagger".to_string(),
-        })
-        .with(Item {})
-        .with(Equippable { slot: EquipmentSlot::Melee })
-        .with(MeleePowerBonus { power: 2 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
+/// Closes the doorways with doors. Some are locked, as long as the stairs down
+/// can still be reached without going through them, and the key to each of
+/// those is left somewhere on the near side.
+pub fn spawn_doors(ecs: &mut World, doors: &[usize], start_idx: usize) {
+    let mut locked: Vec<usize> = Vec::new();
+    let mut keys: Vec<usize> = Vec::new();
+    {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let stairs = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::DownStairs);
+        for door in doors.iter() {
+            if rng.roll_dice(1, LOCKED_DOOR_ONE_IN) != 1 {
+                continue;
+            }
+            locked.push(*door);
+            if !stairs.is_some_and(|stairs| reachable_tiles(&map, start_idx, &locked)[stairs]) {
+                locked.pop();
+            }
+        }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Shield".to_string(),
-        })
-        .with(Item {})
-        .with(Equippable { slot: EquipmentSlot::Shield })
-        .with(DefenseBonus { defense: 1 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
+        let reachable = reachable_tiles(&map, start_idx, &locked);
+        let key_spots: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| {
+                reachable[*idx]
+                    && *idx != start_idx
+                    && map.tiles[*idx] == TileType::Floor
+                    && !doors.contains(idx)
+            })
+            .collect();
+        if !key_spots.is_empty() {
+            for _ in locked.iter() {
+                keys.push(key_spots[rng.range(0, key_spots.len())]);
+            }
+        }
+    }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Longsword".to_string(),
-        })
-        .with(Item {})
-        .with(Equippable { slot: EquipmentSlot::Melee })
-        .with(MeleePowerBonus { power: 4 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+    let raws = RAWS.lock().unwrap();
+    for idx in doors.iter() {
+        let (x, y) = ((*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+        let door = spawn_named_entity(
+            &raws,
+            ecs.create_entity(),
+            "Door",
+            SpawnType::AtPosition { x, y },
+        );
+        if let Some(door) = door.filter(|_| locked.contains(idx)) {
+            if let Some(door) = ecs.write_storage::<Door>().get_mut(door) {
+                door.locked = true;
+            }
+        }
+    }
+    for idx in keys.iter() {
+        let (x, y) = ((*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+        spawn_named_entity(
+            &raws,
+            ecs.create_entity(),
+            "Iron Key",
+            SpawnType::AtPosition { x, y },
+        );
+    }
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Name {
-            name: "Tower Shield".to_string(),
-        })
-        .with(Item {})
-        .with(Equippable { slot: EquipmentSlot::Shield })
-        .with(DefenseBonus { defense: 3 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// The tiles that can be walked to from `start_idx` with the `closed` ones
+/// walled off.
+fn reachable_tiles(map: &Map, start_idx: usize, closed: &[usize]) -> Vec<bool> {
+    let mut map = map.clone();
+    map.populate_blocked();
+    for idx in closed.iter() {
+        map.blocked[*idx] = true;
+    }
+    let dijkstra_map = rltk::DijkstraMap::new(
+        map.width as usize,
+        map.height as usize,
+        &[start_idx],
+        &map,
+        (map.width * map.height) as f32,
+    );
+    dijkstra_map
+        .map
+        .iter()
+        .map(|distance| *distance < f32::MAX)
+        .collect()
 }
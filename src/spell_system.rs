@@ -0,0 +1,162 @@
+use crate::{
+    components::{CombatStats, Name, Position, SpellBook, SpellEffect, WantsToCast},
+    effects::{self, EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
+    map::Map,
+    particle_system::ParticleBuilder,
+    ranged_combat_system::projectile_flight,
+    raws::{spawn_named_entity, SpawnType, RAWS},
+};
+use rltk::{BaseMap, Point};
+use specs::prelude::*;
+
+/// How long a bolt takes to cross a tile.
+const MS_PER_TILE: f32 = 40.0;
+
+/// Casts the spells monsters chose this turn, and starts their cooldowns.
+/// Bolts fly like arrows and hit the first creature in their way, summoned
+/// monsters appear next to the caster.
+pub struct SpellSystem {}
+
+impl<'a> System<'a> for SpellSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, WantsToCast>,
+        WriteStorage<'a, SpellBook>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CombatStats>,
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, ParticleBuilder>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut log,
+            mut wants_cast,
+            mut spellbooks,
+            names,
+            positions,
+            combat_stats,
+            mut map,
+            mut effects,
+            mut particle_builder,
+            lazy,
+        ) = data;
+
+        for (entity, cast, book, name, pos) in
+            (&entities, &wants_cast, &mut spellbooks, &names, &positions).join()
+        {
+            let Some(spell) = book.spells.get_mut(cast.spell) else {
+                continue;
+            };
+            spell.ready_in = spell.cooldown;
+            let start = Point::new(pos.x, pos.y);
+
+            match &spell.effect {
+                SpellEffect::Bolt { damage } => {
+                    let Some(target) = positions.get(cast.target) else {
+                        continue;
+                    };
+                    let (flight, victim) = projectile_flight(
+                        &map,
+                        &combat_stats,
+                        start,
+                        Point::new(target.x, target.y),
+                    );
+                    particle_builder.request_path(
+                        &flight,
+                        rltk::RGB::named(rltk::ORANGE),
+                        rltk::to_cp437('*'),
+                        MS_PER_TILE,
+                    );
+                    let logger = Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("casts")
+                        .item(&spell.name);
+                    match victim {
+                        Some(victim) => {
+                            logger
+                                .append("at")
+                                .name(&names.get(victim).unwrap().name)
+                                .append(", for")
+                                .damage(*damage)
+                                .append("hp")
+                                .log(&mut log);
+                            effects.push(
+                                Some(entity),
+                                EffectType::Damage { amount: *damage },
+                                Targets::Single { target: victim },
+                            );
+                        }
+                        None => logger.append("and misses").log(&mut log),
+                    }
+                }
+                SpellEffect::HealAlly { amount } => {
+                    let Some(ally) = names.get(cast.target) else {
+                        continue;
+                    };
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("casts")
+                        .item(&spell.name)
+                        .append("on")
+                        .name(&ally.name)
+                        .append(", healing")
+                        .healing(*amount)
+                        .append("hp")
+                        .log(&mut log);
+                    effects.push(
+                        Some(entity),
+                        EffectType::Healing { amount: *amount },
+                        Targets::Single {
+                            target: cast.target,
+                        },
+                    );
+                    effects.push(
+                        Some(entity),
+                        effects::particle('♥', rltk::GREEN),
+                        Targets::Single {
+                            target: cast.target,
+                        },
+                    );
+                }
+                SpellEffect::Summon { mob } => {
+                    let here = map.get_index_at(pos.x, pos.y);
+                    let Some((idx, _)) = map.get_available_exits(here).into_iter().next() else {
+                        continue;
+                    };
+                    // taken now, so that two summons don't share it
+                    map.blocked[idx] = true;
+                    let (x, y) = map.get_xy_from_idx(idx);
+                    Logger::new(LogCategory::Combat)
+                        .name(&name.name)
+                        .append("casts")
+                        .item(&spell.name)
+                        .append("!")
+                        .log(&mut log);
+                    effects.push(
+                        Some(entity),
+                        effects::particle('*', rltk::MAGENTA),
+                        Targets::Tile { tile_idx: idx },
+                    );
+                    let mob = mob.clone();
+                    lazy.exec_mut(move |world| {
+                        spawn_named_entity(
+                            &RAWS.lock().unwrap(),
+                            world.create_entity(),
+                            &mob,
+                            SpawnType::AtPosition { x, y },
+                        );
+                    });
+                }
+            }
+        }
+
+        wants_cast.clear();
+    }
+}
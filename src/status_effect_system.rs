@@ -0,0 +1,83 @@
+use super::{
+    effects::{EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
+    RunState, StatusEffects, StatusKind, Viewshed,
+};
+use specs::prelude::*;
+
+/// Ticks statuses once per player turn, like the hunger clocks: poison and
+/// burning hurt, on behalf of whoever inflicted them, regeneration heals, and
+/// expired statuses are removed.
+/// Haste, slow, blindness and confusion are read where they matter.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, StatusEffects>,
+        WriteExpect<'a, EffectQueue>,
+        WriteStorage<'a, Viewshed>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut statuses, mut effects, mut viewsheds, player_entity, runstate, mut log) =
+            data;
+
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, statuses) in (&entities, &mut statuses).join() {
+            let is_player = entity == *player_entity;
+            for effect in statuses.effects.iter_mut() {
+                match effect.kind {
+                    StatusKind::Poison | StatusKind::Burning => {
+                        effects.push(
+                            effect.inflicted_by.or(Some(entity)),
+                            EffectType::Damage {
+                                amount: effect.potency,
+                            },
+                            Targets::Single { target: entity },
+                        );
+                        if is_player {
+                            Logger::new(LogCategory::Combat)
+                                .append(format!("You are {}, you suffer", effect.kind.label()))
+                                .damage(effect.potency)
+                                .append("hp.")
+                                .log(&mut log);
+                        }
+                    }
+                    StatusKind::Regeneration => {
+                        effects.push(
+                            Some(entity),
+                            EffectType::Healing {
+                                amount: effect.potency,
+                            },
+                            Targets::Single { target: entity },
+                        );
+                    }
+                    _ => {}
+                }
+
+                effect.turns -= 1;
+                if effect.turns > 0 {
+                    continue;
+                }
+                if effect.kind == StatusKind::Blindness {
+                    if let Some(viewshed) = viewsheds.get_mut(entity) {
+                        viewshed.dirty = true;
+                    }
+                }
+                if is_player {
+                    Logger::new(LogCategory::System)
+                        .append(format!("You are no longer {}.", effect.kind.label()))
+                        .log(&mut log);
+                }
+            }
+            statuses.effects.retain(|effect| effect.turns > 0);
+        }
+    }
+}
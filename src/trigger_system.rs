@@ -0,0 +1,65 @@
+use super::{
+    effects::{EffectQueue, EffectType, Targets},
+    gamelog::{GameLog, LogCategory, Logger},
+    EntityMoved, EntryTrigger, Hidden, Map, Name, Position,
+};
+use specs::prelude::*;
+
+/// Sets off the traps on the tiles entities stepped onto this turn. A trap
+/// that goes off is no longer hidden, whether the player saw it or not.
+pub struct TriggerSystem {}
+
+impl<'a> System<'a> for TriggerSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, EntityMoved>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, EntryTrigger>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            entities,
+            mut entity_moved,
+            positions,
+            entry_triggers,
+            mut hidden,
+            names,
+            mut effects,
+            mut log,
+        ) = data;
+
+        for (entity, _moved, pos) in (&entities, &entity_moved, &positions).join() {
+            let idx = map.get_index_at(pos.x, pos.y);
+            for trigger in map.tile_content[idx].iter() {
+                if *trigger == entity || !entry_triggers.contains(*trigger) {
+                    continue;
+                }
+                hidden.remove(*trigger);
+                if map.visible_tiles[idx] {
+                    if let (Some(name), Some(trap)) = (names.get(entity), names.get(*trigger)) {
+                        Logger::new(LogCategory::Combat)
+                            .name(&name.name)
+                            .append("sets off the")
+                            .item(&trap.name)
+                            .append("!")
+                            .log(&mut log);
+                    }
+                }
+                effects.push(
+                    Some(*trigger),
+                    EffectType::TriggerFire { trigger: *trigger },
+                    Targets::Single { target: entity },
+                );
+            }
+        }
+
+        entity_moved.clear();
+    }
+}
@@ -1,7 +1,15 @@
-use super::{Map, Player, Position, Viewshed};
+use super::{
+    gamelog::{GameLog, LogCategory, Logger},
+    gamesystem::{equipment_bonus, spots_hidden},
+    Attributes, EquipmentBonus, Equipped, Hidden, Map, Name, Player, Position, StatusEffects,
+    StatusKind, Viewshed,
+};
 use rltk::{field_of_view, Point};
 use specs::prelude::*;
 
+/// How far the blind can still feel their way around.
+const BLIND_RANGE: i32 = 1;
+
 pub struct VisibilitySystem {}
 
 impl<'a> System<'a> for VisibilitySystem {
@@ -11,16 +19,44 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Player>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, EquipmentBonus>,
+        ReadStorage<'a, StatusEffects>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, Attributes>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, rltk::RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (
+            mut map,
+            entities,
+            mut viewshed,
+            pos,
+            player,
+            equipped,
+            bonuses,
+            statuses,
+            mut hidden,
+            attributes,
+            names,
+            mut rng,
+            mut log,
+        ) = data;
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
                 viewshed.visible_tiles.clear();
-                viewshed.visible_tiles =
-                    field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
+                let mut range = viewshed.range + equipment_bonus(ent, &equipped, &bonuses).vision;
+                if statuses
+                    .get(ent)
+                    .is_some_and(|s| s.has(StatusKind::Blindness))
+                {
+                    range = BLIND_RANGE;
+                }
+                viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), range, &*map);
                 viewshed
                     .visible_tiles
                     .retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
@@ -32,10 +68,25 @@ impl<'a> System<'a> for VisibilitySystem {
                         *visible_tile = false
                     }
 
+                    let intelligence = attributes.get(ent).map_or(10, |a| a.intelligence);
                     for visible_tile in viewshed.visible_tiles.iter() {
                         let idx = map.get_index_at(visible_tile.x, visible_tile.y);
                         map.revealed_tiles[idx] = true;
                         map.visible_tiles[idx] = true;
+
+                        // a chance to notice hidden things in passing
+                        for other in map.tile_content[idx].iter() {
+                            if hidden.contains(*other) && spots_hidden(&mut rng, intelligence, 0) {
+                                hidden.remove(*other);
+                                if let Some(name) = names.get(*other) {
+                                    Logger::new(LogCategory::System)
+                                        .append("You spot a")
+                                        .item(&name.name)
+                                        .append(".")
+                                        .log(&mut log);
+                                }
+                            }
+                        }
                     }
                 }
             }